@@ -0,0 +1,175 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    codegen::generate_parser_source,
+    grammar_file::{parse_grammar, GrammarError},
+    parser_types::{
+        action_goto::generate_parsing_table, rule::Rule,
+        terminal_or_nonterminal::TerminalOrNonTerminal,
+    },
+};
+
+/// What conflicts in the parsing table do to the build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Conflicts {
+    /// They are reported as warnings, and the parser takes the last action of every cell.
+    #[default]
+    Warn,
+    /// They are reported as warnings and fail the build.
+    Error,
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Grammar {
+        path: PathBuf,
+        error: GrammarError,
+    },
+    /// The number of conflicting cells, with [`Conflicts::Error`].
+    Conflicts(usize),
+    /// The output directory was not given and `OUT_DIR` is not set, as outside a build script.
+    NoOutDir,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            BuildError::Grammar { path, error } => write!(f, "{}:{}", path.display(), error),
+            BuildError::Conflicts(count) => {
+                write!(f, "the parsing table has {} conflicting cells", count)
+            }
+            BuildError::NoOutDir => write!(f, "OUT_DIR is not set"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Compiles a grammar file into a parser from a build script. The grammar, in the format of
+/// [`parse_grammar`], gets its table from [`generate_parsing_table`], the same table a program
+/// reading the grammar at runtime would generate, and the parser written from it by
+/// [`generate_parser_source`] is saved in `OUT_DIR`, to be included with
+/// `include!(concat!(env!("OUT_DIR"), "/name.rs"))`. Cargo is told to run the build script again
+/// when the grammar changes. A build script only needs `Build::new("src/expr.grammar").run()`,
+/// failing on the error it may return.
+///
+/// Table generation is slow and prints the state machine when debug assertions are on, so build
+/// scripts are best compiled with `opt-level = 3` and `debug-assertions = false` under
+/// `[profile.dev.build-override]`.
+pub struct Build {
+    grammar: PathBuf,
+    out_dir: Option<PathBuf>,
+    output: Option<String>,
+    conflicts: Conflicts,
+    expected_conflicts: usize,
+}
+
+impl Build {
+    pub fn new(grammar: impl AsRef<Path>) -> Self {
+        Self {
+            grammar: grammar.as_ref().to_path_buf(),
+            out_dir: None,
+            output: None,
+            conflicts: Conflicts::default(),
+            expected_conflicts: 0,
+        }
+    }
+    /// Writes to `out_dir` instead of `OUT_DIR`.
+    pub fn with_out_dir(mut self, out_dir: impl AsRef<Path>) -> Self {
+        self.out_dir = Some(out_dir.as_ref().to_path_buf());
+        self
+    }
+    /// Names the generated file, by default the name of the grammar file with an `rs` extension.
+    pub fn with_output(mut self, output: &str) -> Self {
+        self.output = Some(output.to_string());
+        self
+    }
+    pub fn with_conflicts(mut self, conflicts: Conflicts) -> Self {
+        self.conflicts = conflicts;
+        self
+    }
+    /// Accepts exactly `count` conflicting cells, like yacc's `%expect`: with that many, none
+    /// are reported.
+    pub fn with_expected_conflicts(mut self, count: usize) -> Self {
+        self.expected_conflicts = count;
+        self
+    }
+    /// Generates the parser and returns the path it was written to.
+    pub fn run(&self) -> Result<PathBuf, BuildError> {
+        println!("cargo:rerun-if-changed={}", self.grammar.display());
+        let out_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => std::env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or(BuildError::NoOutDir)?,
+        };
+        let text = std::fs::read_to_string(&self.grammar).map_err(|error| BuildError::Io {
+            path: self.grammar.clone(),
+            error,
+        })?;
+        let grammar = parse_grammar(&text).map_err(|error| BuildError::Grammar {
+            path: self.grammar.clone(),
+            error,
+        })?;
+        let rules = grammar.rules();
+        let parsing_table = generate_parsing_table(&rules, None);
+
+        let conflicts: Vec<_> = parsing_table.conflicts().collect();
+        if conflicts.len() != self.expected_conflicts {
+            for (state, terminal, actions) in &conflicts {
+                let actions: Vec<String> = actions
+                    .iter()
+                    .map(|action| format!("{:?}", action))
+                    .collect();
+                println!(
+                    "cargo:warning={}: conflict in state {} on {:?}: {}",
+                    self.grammar.display(),
+                    state,
+                    terminal,
+                    actions.join(", ")
+                );
+                for item in parsing_table.kernel(*state) {
+                    let Rule { lhs, rhs } = &rules[item.index];
+                    let mut symbols: Vec<String> = rhs
+                        .iter()
+                        .map(|symbol| match symbol {
+                            TerminalOrNonTerminal::Terminal(t) => format!("{:?}", t),
+                            TerminalOrNonTerminal::NonTerminal(nt) => format!("{:?}", nt),
+                        })
+                        .collect();
+                    symbols.insert(item.dot_index, "•".to_string());
+                    println!("cargo:warning=    {:?} -> {}", lhs, symbols.join(" "));
+                }
+            }
+            if self.conflicts == Conflicts::Error {
+                return Err(BuildError::Conflicts(conflicts.len()));
+            }
+        }
+
+        let output = match &self.output {
+            Some(output) => output.clone(),
+            None => {
+                let stem = self.grammar.file_stem().unwrap_or_default();
+                format!("{}.rs", stem.to_string_lossy())
+            }
+        };
+        let path = out_dir.join(output);
+        let source = generate_parser_source(&rules, &parsing_table);
+        // Rewriting an unchanged file would make cargo rebuild what includes it.
+        if std::fs::read_to_string(&path).ok().as_deref() != Some(source.as_str()) {
+            std::fs::write(&path, source).map_err(|error| BuildError::Io {
+                path: path.clone(),
+                error,
+            })?;
+        }
+        Ok(path)
+    }
+}
@@ -9,8 +9,15 @@ use crate::parser_types::{
 #[serde(rename_all = "snake_case")]
 pub enum Terminal {
     EOF,
+    Error,
     Identifier,
-    Literal,
+    /// An identifier declared by a `typedef` in scope, told apart from [`Terminal::Identifier`]
+    /// by the token source, see [`crate::c99typedefs::TypedefNames`].
+    TypedefName,
+    IntegerConstant,
+    FloatingConstant,
+    CharacterConstant,
+    StringLiteral,
     LeftParenthesis,
     RightParenthesis,
     LeftBracket,
@@ -104,6 +111,12 @@ impl TerminalTrait for Terminal {
     fn eof() -> Self {
         Terminal::EOF
     }
+    fn is_error(&self) -> bool {
+        self == &Terminal::Error
+    }
+    fn error() -> Self {
+        Terminal::Error
+    }
 }
 #[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, Ord, PartialOrd, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -182,6 +195,8 @@ pub enum NonTerminal {
     AssignmentOperator,
     ConditionalExpression,
     DeclarationList,
+    /// Adjacent string literals, which are concatenated.
+    StringLiteralList,
 }
 impl NonTerminalTrait for NonTerminal {
     fn is_start(&self) -> bool {
@@ -200,7 +215,32 @@ pub fn c99rules() -> Vec<Rule<Terminal, NonTerminal>> {
         },
         Rule {
             lhs: NonTerminal::PrimaryExpression,
-            rhs: vec![TerminalOrNonTerminal::Terminal(Terminal::Literal)],
+            rhs: vec![TerminalOrNonTerminal::Terminal(Terminal::IntegerConstant)],
+        },
+        Rule {
+            lhs: NonTerminal::PrimaryExpression,
+            rhs: vec![TerminalOrNonTerminal::Terminal(Terminal::FloatingConstant)],
+        },
+        Rule {
+            lhs: NonTerminal::PrimaryExpression,
+            rhs: vec![TerminalOrNonTerminal::Terminal(Terminal::CharacterConstant)],
+        },
+        Rule {
+            lhs: NonTerminal::PrimaryExpression,
+            rhs: vec![TerminalOrNonTerminal::NonTerminal(
+                NonTerminal::StringLiteralList,
+            )],
+        },
+        Rule {
+            lhs: NonTerminal::StringLiteralList,
+            rhs: vec![TerminalOrNonTerminal::Terminal(Terminal::StringLiteral)],
+        },
+        Rule {
+            lhs: NonTerminal::StringLiteralList,
+            rhs: vec![
+                TerminalOrNonTerminal::NonTerminal(NonTerminal::StringLiteralList),
+                TerminalOrNonTerminal::Terminal(Terminal::StringLiteral),
+            ],
         },
         Rule {
             lhs: NonTerminal::PrimaryExpression,
@@ -850,6 +890,10 @@ pub fn c99rules() -> Vec<Rule<Terminal, NonTerminal>> {
                 NonTerminal::EnumSpecifier,
             )],
         },
+        Rule {
+            lhs: NonTerminal::TypeSpecifier,
+            rhs: vec![TerminalOrNonTerminal::Terminal(Terminal::TypedefName)],
+        },
         Rule {
             lhs: NonTerminal::StructOrUnionSpecifier,
             rhs: vec![
@@ -1517,6 +1561,13 @@ pub fn c99rules() -> Vec<Rule<Terminal, NonTerminal>> {
                 NonTerminal::JumpStatement,
             )],
         },
+        Rule {
+            lhs: NonTerminal::Statement,
+            rhs: vec![
+                TerminalOrNonTerminal::Terminal(Terminal::Error),
+                TerminalOrNonTerminal::Terminal(Terminal::Semicolon),
+            ],
+        },
         Rule {
             lhs: NonTerminal::LabeledStatement,
             rhs: vec![
@@ -1747,6 +1798,13 @@ pub fn c99rules() -> Vec<Rule<Terminal, NonTerminal>> {
             lhs: NonTerminal::ExternalDeclaration,
             rhs: vec![TerminalOrNonTerminal::NonTerminal(NonTerminal::Declaration)],
         },
+        Rule {
+            lhs: NonTerminal::ExternalDeclaration,
+            rhs: vec![
+                TerminalOrNonTerminal::Terminal(Terminal::Error),
+                TerminalOrNonTerminal::Terminal(Terminal::Semicolon),
+            ],
+        },
         Rule {
             lhs: NonTerminal::FunctionDefinition,
             rhs: vec![
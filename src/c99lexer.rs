@@ -0,0 +1,150 @@
+use crate::{c99grammar::Terminal, lexing::lexer::Lexer};
+
+const KEYWORDS: &[(Terminal, &str)] = &[
+    (Terminal::Auto, "auto"),
+    (Terminal::Break, "break"),
+    (Terminal::Case, "case"),
+    (Terminal::Char, "char"),
+    (Terminal::Const, "const"),
+    (Terminal::Continue, "continue"),
+    (Terminal::DefaultCase, "default"),
+    (Terminal::Do, "do"),
+    (Terminal::Double, "double"),
+    (Terminal::Else, "else"),
+    (Terminal::Enum, "enum"),
+    (Terminal::Extern, "extern"),
+    (Terminal::Float, "float"),
+    (Terminal::For, "for"),
+    (Terminal::Goto, "goto"),
+    (Terminal::If, "if"),
+    (Terminal::Inline, "inline"),
+    (Terminal::Int, "int"),
+    (Terminal::Long, "long"),
+    (Terminal::Register, "register"),
+    (Terminal::Restrict, "restrict"),
+    (Terminal::Return, "return"),
+    (Terminal::Short, "short"),
+    (Terminal::Signed, "signed"),
+    (Terminal::SizeOf, "sizeof"),
+    (Terminal::Static, "static"),
+    (Terminal::Struct, "struct"),
+    (Terminal::Switch, "switch"),
+    (Terminal::Typedef, "typedef"),
+    (Terminal::Union, "union"),
+    (Terminal::Unsigned, "unsigned"),
+    (Terminal::Void, "void"),
+    (Terminal::Volatile, "volatile"),
+    (Terminal::While, "while"),
+    (Terminal::Bool, "_Bool"),
+    (Terminal::Complex, "_Complex"),
+    (Terminal::Imaginary, "_Imaginary"),
+];
+
+/// Every punctuator but `#` and `##`, which only appear in preprocessing directives, with the
+/// digraphs.
+const PUNCTUATORS: &[(Terminal, &str)] = &[
+    (Terminal::LeftBracket, "["),
+    (Terminal::RightBracket, "]"),
+    (Terminal::LeftParenthesis, "("),
+    (Terminal::RightParenthesis, ")"),
+    (Terminal::LeftBrace, "{"),
+    (Terminal::RightBrace, "}"),
+    (Terminal::Dot, "."),
+    (Terminal::Arrow, "->"),
+    (Terminal::Increment, "++"),
+    (Terminal::Decrement, "--"),
+    (Terminal::BitwiseAnd, "&"),
+    (Terminal::Multiply, "*"),
+    (Terminal::Plus, "+"),
+    (Terminal::Minus, "-"),
+    (Terminal::Tilde, "~"),
+    (Terminal::Not, "!"),
+    (Terminal::Divide, "/"),
+    (Terminal::Percent, "%"),
+    (Terminal::LeftShift, "<<"),
+    (Terminal::RightShift, ">>"),
+    (Terminal::LessThan, "<"),
+    (Terminal::GreaterThan, ">"),
+    (Terminal::LessThanEquals, "<="),
+    (Terminal::GreaterThanEquals, ">="),
+    (Terminal::EqualsEquals, "=="),
+    (Terminal::NotEquals, "!="),
+    (Terminal::Caret, "^"),
+    (Terminal::Pipe, "|"),
+    (Terminal::LogicalAnd, "&&"),
+    (Terminal::LogicalOr, "||"),
+    (Terminal::QuestionMark, "?"),
+    (Terminal::Colon, ":"),
+    (Terminal::Semicolon, ";"),
+    (Terminal::Ellipsis, "..."),
+    (Terminal::Equals, "="),
+    (Terminal::MultiplyEquals, "*="),
+    (Terminal::DivideEquals, "/="),
+    (Terminal::ModEquals, "%="),
+    (Terminal::PlusEquals, "+="),
+    (Terminal::MinusEquals, "-="),
+    (Terminal::LeftShiftEquals, "<<="),
+    (Terminal::RightShiftEquals, ">>="),
+    (Terminal::BitwiseAndEquals, "&="),
+    (Terminal::ExclusiveOrEquals, "^="),
+    (Terminal::BitwiseOrEquals, "|="),
+    (Terminal::Comma, ","),
+    (Terminal::LeftBracket, "<:"),
+    (Terminal::RightBracket, ":>"),
+    (Terminal::LeftBrace, "<%"),
+    (Terminal::RightBrace, "%>"),
+];
+
+/// An escape sequence, or a backslash-newline line continuation.
+const ESCAPE: &str = r"\\(\r\n|.|\n)";
+
+/// A lexer for C99 source. All identifiers come out as [`Terminal::Identifier`]; pass the tokens
+/// through [`crate::c99typedefs::TypedefNames::classify`] to tell typedef names apart.
+///
+/// No preprocessing is done: directives are skipped whole, line continuations included, and
+/// macros are left unexpanded. Line continuations are also skipped between tokens and inside
+/// comments and literals, but not in the middle of other tokens. Adjacent string literals are
+/// separate tokens, concatenated by the grammar.
+pub fn c99lexer() -> Lexer<Terminal> {
+    let mut builder = Lexer::builder();
+    for (terminal, keyword) in KEYWORDS {
+        builder = builder.with_literal(*terminal, keyword);
+    }
+    for (terminal, punctuator) in PUNCTUATORS {
+        builder = builder.with_literal(*terminal, punctuator);
+    }
+    let universal = r"\\u[0-9a-fA-F]{4}|\\U[0-9a-fA-F]{8}";
+    let integer_suffix = "([uU](l|L|ll|LL)?|(l|L|ll|LL)[uU]?)?";
+    let exponent = "[eE][+-]?[0-9]+";
+    builder
+        .with_token(
+            Terminal::Identifier,
+            &format!("([a-zA-Z_]|{universal})([a-zA-Z_0-9]|{universal})*"),
+        )
+        .with_token(
+            Terminal::IntegerConstant,
+            &format!("([1-9][0-9]*|0[0-7]*|0[xX][0-9a-fA-F]+){integer_suffix}"),
+        )
+        .with_token(
+            Terminal::FloatingConstant,
+            &format!(
+                "(([0-9]*\\.[0-9]+|[0-9]+\\.)({exponent})?|[0-9]+{exponent}\
+                |0[xX]([0-9a-fA-F]*\\.[0-9a-fA-F]+|[0-9a-fA-F]+\\.?)[pP][+-]?[0-9]+)[flFL]?"
+            ),
+        )
+        .with_token(
+            Terminal::CharacterConstant,
+            &format!("L?'([^'\\\\\\n]|{ESCAPE})+'"),
+        )
+        .with_token(
+            Terminal::StringLiteral,
+            &format!("L?\"([^\"\\\\\\n]|{ESCAPE})*\""),
+        )
+        .with_skip(r"[ \t\n\v\f\r]+")
+        .with_skip(r"\\\r?\n")
+        .with_skip(&format!("//([^\\\\\\n]|{ESCAPE})*"))
+        .with_skip(r"/\*([^*]|\*+[^*/])*\*+/")
+        .with_skip(&format!("(#|%:)([^\\\\\\n]|{ESCAPE})*"))
+        .build()
+        .expect("the C99 token definitions are valid")
+}
@@ -0,0 +1,156 @@
+use std::{cell::RefCell, collections::BTreeSet, rc::Rc};
+
+use crate::{
+    c99grammar::{NonTerminal, Terminal},
+    parser_types::{
+        parse_tree::ParseTree, reduce_hook::ReduceHook, stack::Stack,
+        terminal_or_nonterminal::TerminalOrNonTerminal, token::Token,
+    },
+};
+
+/// The typedef names in scope while parsing C, the symbol table behind the "lexer hack". The same
+/// table is consulted by the token source, through [`TypedefNames::classify`], and filled in by
+/// the parser, as a [`ReduceHook`], so clones share their contents. Pass one clone to
+/// `Parser::with_reduce_hook` and parse the tokens from [`TypedefNames::classify`] on another.
+///
+/// A scope is opened at every `{` and closed at the matching `}`, so names declared in a
+/// compound statement go out of scope with it. Redeclaring a typedef name as an ordinary
+/// identifier in an inner scope is not supported, as the token source has already classified it
+/// by the time the declaration is parsed.
+#[derive(Debug, Clone)]
+pub struct TypedefNames {
+    scopes: Rc<RefCell<Vec<BTreeSet<String>>>>,
+}
+
+impl Default for TypedefNames {
+    fn default() -> Self {
+        Self {
+            scopes: Rc::new(RefCell::new(vec![BTreeSet::new()])),
+        }
+    }
+}
+
+impl TypedefNames {
+    pub fn contains(&self, name: &str) -> bool {
+        self.scopes
+            .borrow()
+            .iter()
+            .any(|scope| scope.contains(name))
+    }
+    /// Declares `name` as a typedef name in the innermost scope.
+    pub fn insert(&self, name: impl Into<String>) {
+        self.scopes
+            .borrow_mut()
+            .last_mut()
+            .unwrap()
+            .insert(name.into());
+    }
+    pub fn push_scope(&self) {
+        self.scopes.borrow_mut().push(BTreeSet::new());
+    }
+    /// Closes the innermost scope. The file scope is never closed.
+    pub fn pop_scope(&self) {
+        let mut scopes = self.scopes.borrow_mut();
+        if scopes.len() > 1 {
+            scopes.pop();
+        }
+    }
+    /// Turns identifiers that are typedef names in scope into [`Terminal::TypedefName`] tokens,
+    /// opening and closing scopes at braces. Tokens are classified as they are pulled, so the
+    /// table must be up to date by then.
+    pub fn classify<I: Iterator<Item = Token<Terminal>>>(&self, tokens: I) -> Classify<I> {
+        Classify {
+            typedef_names: self.clone(),
+            tokens,
+        }
+    }
+}
+
+/// The iterator returned by [`TypedefNames::classify`].
+pub struct Classify<I> {
+    typedef_names: TypedefNames,
+    tokens: I,
+}
+
+impl<I: Iterator<Item = Token<Terminal>>> Iterator for Classify<I> {
+    type Item = Token<Terminal>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut token = self.tokens.next()?;
+        match token.kind {
+            Terminal::LeftBrace => self.typedef_names.push_scope(),
+            Terminal::RightBrace => self.typedef_names.pop_scope(),
+            Terminal::Identifier if self.typedef_names.contains(&token.lexeme) => {
+                token.kind = Terminal::TypedefName
+            }
+            _ => {}
+        }
+        Some(token)
+    }
+}
+
+/// Registers the declarators of a declaration whose specifiers include `typedef`. Each one is
+/// reduced to an `InitDeclarator` as soon as the `,` or `;` after it is read, before the next
+/// identifier is classified.
+impl ReduceHook<Terminal, NonTerminal> for TypedefNames {
+    fn on_reduce(
+        &mut self,
+        node: &ParseTree<Terminal, NonTerminal>,
+        below: &Stack<ParseTree<Terminal, NonTerminal>>,
+    ) {
+        let ParseTree::Node {
+            lhs: NonTerminal::InitDeclarator,
+            ..
+        } = node
+        else {
+            return;
+        };
+        // Below the first declarator are the specifiers, below later ones a comma and the
+        // declarators before them.
+        let mut below = below.iter();
+        let specifiers = match (below.next(), below.next(), below.next()) {
+            (Some(ParseTree::Leaf(_)), Some(list), Some(specifiers))
+                if list.symbol()
+                    == TerminalOrNonTerminal::NonTerminal(NonTerminal::InitDeclaratorList) =>
+            {
+                specifiers
+            }
+            (Some(specifiers), ..) => specifiers,
+            (None, ..) => return,
+        };
+        if specifiers.symbol()
+            != TerminalOrNonTerminal::NonTerminal(NonTerminal::DeclarationSpecifiers)
+            || !specifiers
+                .tokens()
+                .iter()
+                .any(|token| token.kind == Terminal::Typedef)
+        {
+            return;
+        }
+        if let Some(name) = declared_name(node) {
+            self.insert(name.lexeme.clone());
+        }
+    }
+}
+
+/// The identifier an `InitDeclarator`, `Declarator` or `DirectDeclarator` declares.
+fn declared_name(tree: &ParseTree<Terminal, NonTerminal>) -> Option<&Token<Terminal>> {
+    let mut tree = tree;
+    loop {
+        let ParseTree::Node { lhs, children, .. } = tree else {
+            return None;
+        };
+        tree = match (lhs, children.as_slice()) {
+            (NonTerminal::InitDeclarator, [declarator, ..]) => declarator,
+            (NonTerminal::Declarator, [.., direct_declarator]) => direct_declarator,
+            (NonTerminal::DirectDeclarator, [ParseTree::Leaf(token), ..])
+                if token.kind == Terminal::Identifier =>
+            {
+                return Some(token)
+            }
+            (NonTerminal::DirectDeclarator, [ParseTree::Leaf(_), declarator, ..]) => declarator,
+            (NonTerminal::DirectDeclarator, [direct_declarator, ..]) => direct_declarator,
+            _ => return None,
+        };
+    }
+}
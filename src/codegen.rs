@@ -0,0 +1,464 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use serde::Serialize;
+
+use crate::parser_types::{
+    action_goto::{Action, ParsingTable},
+    nonterminal::NonTerminalTrait,
+    rule::Rule,
+    terminal::TerminalTrait,
+    terminal_or_nonterminal::TerminalOrNonTerminal,
+};
+
+/// The driver of a generated parser, written after its tables. It only relies on the items the
+/// generator writes before it.
+const DRIVER: &str = r#"
+/// An action of the parsing table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum Action {
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+}
+
+/// The action `state` takes on `terminal`, if any.
+#[allow(dead_code)]
+pub fn action(state: usize, terminal: Terminal) -> Option<Action> {
+    match ACTION[ACTION_ROWS[state] as usize][terminal as usize] {
+        0 => None,
+        1 => Some(Action::Accept),
+        n if n % 2 == 0 => Some(Action::Shift(n as usize / 2 - 1)),
+        n => Some(Action::Reduce(n as usize / 2 - 1)),
+    }
+}
+
+/// The state reached from `state` on `nonterminal`, if any.
+#[allow(dead_code)]
+pub fn goto(state: usize, nonterminal: NonTerminal) -> Option<usize> {
+    match GOTO[GOTO_ROWS[state] as usize][nonterminal as usize] {
+        0 => None,
+        n => Some(n as usize - 1),
+    }
+}
+
+/// The state parsing `entry_point` starts from, if it can be parsed on its own.
+#[allow(dead_code)]
+pub fn entry_state(entry_point: NonTerminal) -> Option<usize> {
+    ENTRY_STATES
+        .iter()
+        .find(|(nonterminal, _)| *nonterminal == entry_point)
+        .map(|(_, state)| *state)
+}
+
+/// Every terminal with an action in `state`, but the error pseudo-terminal.
+#[allow(dead_code)]
+pub fn expected(state: usize) -> Vec<Terminal> {
+    TERMINALS
+        .iter()
+        .copied()
+        .filter(|terminal| *terminal != ERROR && action(state, *terminal).is_some())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub enum ParseTree<T> {
+    Leaf(T),
+    Node {
+        lhs: NonTerminal,
+        /// The number of the reduced rule.
+        rule: usize,
+        children: Vec<ParseTree<T>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub enum ParseError<T> {
+    NotAnEntryPoint(NonTerminal),
+    /// `token` can't come next. A missing token is the end of the input.
+    Unexpected {
+        token: Option<T>,
+        state: usize,
+        expected: Vec<Terminal>,
+    },
+}
+
+/// Parses `tokens` from `entry_point`, `kind` giving the terminal of every token. The input ends
+/// with the first token of the end-of-input terminal, or else with the last token. Parsing stops
+/// at the first error.
+#[allow(dead_code)]
+pub fn parse<T>(
+    tokens: impl IntoIterator<Item = T>,
+    kind: impl Fn(&T) -> Terminal,
+    entry_point: NonTerminal,
+) -> Result<ParseTree<T>, ParseError<T>> {
+    let Some(start) = entry_state(entry_point) else {
+        return Err(ParseError::NotAnEntryPoint(entry_point));
+    };
+    let mut states = vec![start];
+    let mut trees: Vec<ParseTree<T>> = vec![];
+    let mut tokens = tokens.into_iter();
+    let mut lookahead = tokens.next();
+    loop {
+        let state = *states.last().unwrap();
+        let terminal = lookahead.as_ref().map_or(EOF, &kind);
+        match (action(state, terminal), lookahead.take()) {
+            (Some(Action::Shift(next)), Some(token)) => {
+                states.push(next);
+                trees.push(ParseTree::Leaf(token));
+                lookahead = tokens.next();
+            }
+            (Some(Action::Reduce(rule)), token) => {
+                let len = RULE_LEN[rule];
+                states.truncate(states.len() - len);
+                let children = trees.split_off(trees.len() - len);
+                let lhs = RULE_LHS[rule];
+                trees.push(ParseTree::Node {
+                    lhs,
+                    rule,
+                    children,
+                });
+                let state = *states.last().unwrap();
+                states.push(goto(state, lhs).expect("every reduction has a goto"));
+                lookahead = token;
+            }
+            (Some(Action::Accept), _) => return Ok(trees.pop().unwrap()),
+            (_, token) => {
+                return Err(ParseError::Unexpected {
+                    token,
+                    state,
+                    expected: expected(state),
+                })
+            }
+        }
+    }
+}
+"#;
+
+/// The keywords of Rust, which are only identifiers written as raw identifiers.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// The keywords that can't be raw identifiers either.
+const RESERVED: &[&str] = &["_", "crate", "self", "Self", "super"];
+
+/// The names of the variants of a generated enum: the `Debug` text of every value, stripped of
+/// what can't be in an identifier and written as a raw identifier if it is a keyword, or `prefix`
+/// and its number where that leaves no identifier or one already taken.
+fn variant_names<T: std::fmt::Debug>(values: &[T], prefix: &str) -> Vec<String> {
+    let mut taken = BTreeSet::new();
+    let mut res = vec![];
+    for (index, value) in values.iter().enumerate() {
+        let name: String = format!("{:?}", value)
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && !RESERVED.contains(&name.as_str());
+        let name = if valid && !taken.contains(&name) {
+            name
+        } else {
+            let mut name = format!("{}{}", prefix, index);
+            while taken.contains(&name) {
+                name.push('_');
+            }
+            name
+        };
+        taken.insert(name.clone());
+        if KEYWORDS.contains(&name.as_str()) {
+            res.push(format!("r#{}", name));
+        } else {
+            res.push(name);
+        }
+    }
+    res
+}
+
+/// Writes `rows` as a static table of distinct rows, and the row of every state as another.
+fn write_rows(out: &mut String, name: &str, rows: Vec<Vec<u32>>, width: usize) {
+    let mut distinct: BTreeMap<Vec<u32>, usize> = BTreeMap::new();
+    let mut order = vec![];
+    let indices: Vec<usize> = rows
+        .into_iter()
+        .map(|row| {
+            let next = distinct.len();
+            *distinct.entry(row.clone()).or_insert_with(|| {
+                order.push(row);
+                next
+            })
+        })
+        .collect();
+    writeln!(out, "#[allow(dead_code)]").unwrap();
+    writeln!(
+        out,
+        "static {}: [[u32; {}]; {}] = [",
+        name,
+        width,
+        order.len()
+    )
+    .unwrap();
+    for row in order {
+        let cells: Vec<String> = row.iter().map(|cell| cell.to_string()).collect();
+        writeln!(out, "    [{}],", cells.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out, "#[allow(dead_code)]").unwrap();
+    writeln!(out, "static {}_ROWS: [u32; {}] = [", name, indices.len()).unwrap();
+    for chunk in indices.chunks(16) {
+        let cells: Vec<String> = chunk.iter().map(|cell| cell.to_string()).collect();
+        writeln!(out, "    {},", cells.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+}
+
+/// Writes a Rust module parsing with `parsing_table`, generated from `rules`, that compiles on its
+/// own: `Terminal` and `NonTerminal` enums named after the `Debug` text of the grammar's, the
+/// action and goto tables as static arrays with identical rows shared, the length and left-hand
+/// side of every rule, and a driver building a parse tree. Cells with conflicts keep the action
+/// [`ParsingTable::action`] returns. The driver does no error recovery.
+///
+/// The module is meant to be written to a file and included with `include!`, for instance in a
+/// `mod` of its own. Every item allows dead code, as a program seldom uses all of them.
+pub fn generate_parser_source<
+    Terminal: std::fmt::Debug
+        + Serialize
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>(
+    rules: &[Rule<Terminal, NonTerminal>],
+    parsing_table: &ParsingTable<Terminal, NonTerminal>,
+) -> String {
+    let mut terminals = BTreeSet::from([Terminal::eof(), Terminal::error()]);
+    terminals.extend(parsing_table.terminals());
+    let mut nonterminals = BTreeSet::new();
+    for rule in rules {
+        nonterminals.insert(rule.lhs);
+        for symbol in &rule.rhs {
+            match symbol {
+                TerminalOrNonTerminal::Terminal(t) => {
+                    terminals.insert(*t);
+                }
+                TerminalOrNonTerminal::NonTerminal(nt) => {
+                    nonterminals.insert(*nt);
+                }
+            }
+        }
+    }
+    let terminals: Vec<Terminal> = terminals.into_iter().collect();
+    let nonterminals: Vec<NonTerminal> = nonterminals.into_iter().collect();
+    let terminal_names = variant_names(&terminals, "Terminal");
+    let nonterminal_names = variant_names(&nonterminals, "NonTerminal");
+    let nonterminal_index: BTreeMap<NonTerminal, usize> = nonterminals
+        .iter()
+        .enumerate()
+        .map(|(index, nt)| (*nt, index))
+        .collect();
+    let states = parsing_table
+        .action_cells()
+        .map(|(state, _, _)| state + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "// Generated by lr-1-parser from {} rules, {} states. Do not edit.",
+        rules.len(),
+        states
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+    for (kind, names, all) in [
+        ("Terminal", &terminal_names, "TERMINALS"),
+        ("NonTerminal", &nonterminal_names, "NONTERMINALS"),
+    ] {
+        writeln!(
+            out,
+            "#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]"
+        )
+        .unwrap();
+        // The names are the grammar's, whatever their case.
+        writeln!(
+            out,
+            "#[allow(dead_code, non_camel_case_types, clippy::upper_case_acronyms)]"
+        )
+        .unwrap();
+        writeln!(out, "pub enum {} {{", kind).unwrap();
+        for name in names {
+            writeln!(out, "    {},", name).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "#[allow(dead_code)]").unwrap();
+        writeln!(out, "pub static {}: [{}; {}] = [", all, kind, names.len()).unwrap();
+        for name in names {
+            writeln!(out, "    {}::{},", kind, name).unwrap();
+        }
+        writeln!(out, "];").unwrap();
+        writeln!(out).unwrap();
+    }
+    let eof = terminals.iter().position(|t| t.is_eof()).unwrap();
+    writeln!(out, "#[allow(dead_code)]").unwrap();
+    writeln!(
+        out,
+        "pub const EOF: Terminal = Terminal::{};",
+        terminal_names[eof]
+    )
+    .unwrap();
+    let error = terminals.iter().position(|t| t.is_error()).unwrap();
+    writeln!(out, "#[allow(dead_code)]").unwrap();
+    writeln!(
+        out,
+        "pub const ERROR: Terminal = Terminal::{};",
+        terminal_names[error]
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+
+    // 0 is no action, 1 accepting, and the others shifts and reductions, alternately.
+    let actions = (0..states)
+        .map(|state| {
+            terminals
+                .iter()
+                .map(|terminal| match parsing_table.action(state, *terminal) {
+                    None => 0,
+                    Some(Action::Accept) => 1,
+                    Some(Action::Shift(next)) => 2 * next as u32 + 2,
+                    Some(Action::Reduce(rule)) => 2 * rule as u32 + 3,
+                })
+                .collect()
+        })
+        .collect();
+    write_rows(&mut out, "ACTION", actions, terminals.len());
+    // 0 is no goto, the others the next state plus one.
+    let gotos = (0..states)
+        .map(|state| {
+            nonterminals
+                .iter()
+                .map(|nt| {
+                    parsing_table
+                        .goto(state, *nt)
+                        .map_or(0, |next| next as u32 + 1)
+                })
+                .collect()
+        })
+        .collect();
+    write_rows(&mut out, "GOTO", gotos, nonterminals.len());
+
+    writeln!(out, "#[allow(dead_code)]").unwrap();
+    writeln!(out, "static RULE_LEN: [usize; {}] = [", rules.len()).unwrap();
+    for chunk in rules.chunks(16) {
+        let lens: Vec<String> = chunk
+            .iter()
+            .map(|rule| rule.rhs.len().to_string())
+            .collect();
+        writeln!(out, "    {},", lens.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out, "#[allow(dead_code)]").unwrap();
+    writeln!(out, "static RULE_LHS: [NonTerminal; {}] = [", rules.len()).unwrap();
+    for rule in rules {
+        let name = &nonterminal_names[nonterminal_index[&rule.lhs]];
+        writeln!(out, "    NonTerminal::{},", name).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    let entry_points = parsing_table.entry_points();
+    writeln!(out, "#[allow(dead_code)]").unwrap();
+    writeln!(
+        out,
+        "static ENTRY_STATES: [(NonTerminal, usize); {}] = [",
+        entry_points.len()
+    )
+    .unwrap();
+    for entry_point in entry_points {
+        let name = &nonterminal_names[nonterminal_index[&entry_point]];
+        let state = parsing_table.entry_state(entry_point).unwrap();
+        writeln!(out, "    (NonTerminal::{}, {}),", name, state).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    out.push_str(DRIVER);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::generate_parser_source;
+    use crate::{grammar_file::parse_grammar, parser_types::action_goto::generate_parsing_table};
+
+    /// Names that are Rust keywords, can't be identifiers, or are the name the generator falls
+    /// back to for `super`.
+    const GRAMMAR: &str = "
+        fn -> if Cond then fn | while Cond fn | type | return Terminal9 ;
+        Cond -> self | Self | crate | super | NonTerminal1 ;
+        NonTerminal1 -> num ;
+    ";
+
+    #[test]
+    fn generated_source_compiles() {
+        let grammar = parse_grammar(GRAMMAR).unwrap();
+        let rules = grammar.rules();
+        let parsing_table = generate_parsing_table(&rules, None);
+        let source = generate_parser_source(&rules, &parsing_table);
+        let main = "
+            fn main() {
+                use Terminal::*;
+                let tokens = [r#if, num, then, r#while, num, r#return, Terminal9];
+                let tree = parse(tokens, |t| *t, NonTerminal::r#fn);
+                assert!(tree.is_ok(), \"{:?} {:?}\", tokens, tree);
+            }
+        ";
+        let dir = std::env::temp_dir().join(format!("lr-1-parser-codegen-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("parser.rs");
+        std::fs::write(&path, source + main).unwrap();
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let output = Command::new(rustc)
+            .args(["--edition", "2021", "-D", "warnings", "-o"])
+            .arg(dir.join("parser"))
+            .arg(&path)
+            .output()
+            .unwrap();
+        let status = output
+            .status
+            .success()
+            .then(|| Command::new(dir.join("parser")).status().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(status.unwrap().success());
+    }
+}
@@ -0,0 +1,253 @@
+use std::{collections::BTreeSet, fmt, iter::Peekable, str::CharIndices};
+
+use serde::Serialize;
+
+use crate::parser_types::{
+    augmented_grammar::AugmentedGrammar, nonterminal::NonTerminalTrait, rule::Rule,
+    terminal::TerminalTrait, terminal_or_nonterminal::TerminalOrNonTerminal, token::Position,
+};
+
+/// A terminal of a grammar read from text, by name.
+#[derive(Copy, Clone, PartialEq, Hash, Eq, Ord, PartialOrd, Serialize)]
+pub struct TerminalName<'g>(pub &'g str);
+
+impl fmt::Debug for TerminalName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl TerminalTrait for TerminalName<'_> {
+    fn is_eof(&self) -> bool {
+        self.0 == "EOF"
+    }
+    fn eof() -> Self {
+        TerminalName("EOF")
+    }
+    fn is_error(&self) -> bool {
+        self.0 == "Error"
+    }
+    fn error() -> Self {
+        TerminalName("Error")
+    }
+}
+
+/// A nonterminal of a grammar read from text, by name.
+#[derive(Copy, Clone, PartialEq, Hash, Eq, Ord, PartialOrd, Serialize)]
+pub struct NonTerminalName<'g>(pub &'g str);
+
+impl fmt::Debug for NonTerminalName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl NonTerminalTrait for NonTerminalName<'_> {
+    fn is_start(&self) -> bool {
+        self.0 == "Start"
+    }
+    fn start() -> Self {
+        NonTerminalName("Start")
+    }
+}
+
+/// The names that stand for the symbols every grammar has, and can't be given to others.
+const RESERVED: &[&str] = &["EOF", "Error", "Start"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrammarError {
+    pub position: Position,
+    pub message: String,
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.position.line, self.position.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for GrammarError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Piece<'g> {
+    Name(&'g str),
+    Directive(&'g str),
+    Arrow,
+    Pipe,
+    Semicolon,
+}
+
+struct GrammarLexer<'g> {
+    text: &'g str,
+    chars: Peekable<CharIndices<'g>>,
+}
+
+impl<'g> GrammarLexer<'g> {
+    fn position(&self, offset: usize) -> Position {
+        Position::default().advance(&self.text[..offset])
+    }
+    fn error(&self, offset: usize, message: impl Into<String>) -> GrammarError {
+        GrammarError {
+            position: self.position(offset),
+            message: message.into(),
+        }
+    }
+    /// The name from `start` to `end` and the name characters after it.
+    fn name(&mut self, start: usize, mut end: usize) -> &'g str {
+        while let Some((offset, c)) = self
+            .chars
+            .next_if(|(_, c)| c.is_alphanumeric() || *c == '_')
+        {
+            end = offset + c.len_utf8();
+        }
+        &self.text[start..end]
+    }
+    /// The next piece and the offset it starts at, skipping whitespace and comments.
+    fn next(&mut self) -> Result<Option<(usize, Piece<'g>)>, GrammarError> {
+        while let Some((offset, c)) = self.chars.next() {
+            let piece = match c {
+                c if c.is_whitespace() => continue,
+                '/' if self.chars.next_if(|(_, c)| *c == '/').is_some() => {
+                    while self.chars.next_if(|(_, c)| *c != '\n').is_some() {}
+                    continue;
+                }
+                '-' if self.chars.next_if(|(_, c)| *c == '>').is_some() => Piece::Arrow,
+                '|' => Piece::Pipe,
+                ';' => Piece::Semicolon,
+                '%' => match self.name(offset + 1, offset + 1) {
+                    "" => return Err(self.error(offset, "expected a directive after `%`")),
+                    name => Piece::Directive(name),
+                },
+                c if c.is_alphabetic() || c == '_' => {
+                    Piece::Name(self.name(offset, offset + c.len_utf8()))
+                }
+                c => return Err(self.error(offset, format!("unexpected `{}`", c))),
+            };
+            return Ok(Some((offset, piece)));
+        }
+        Ok(None)
+    }
+}
+
+/// Reads a grammar written as rules of the form `Lhs -> A b C | D ;`, every alternative a
+/// rule of its own, in the order they are written. The names on the left of a rule are the
+/// nonterminals, every other name is a terminal, and `error` is the error pseudo-terminal.
+/// `//` starts a comment.
+///
+/// `%start Name` sets the nonterminal the start rule derives, by default the first one with
+/// rules, and `%entry Name...` adds entry points. `EOF`, `Error` and `Start` are reserved for
+/// the end of input, the error pseudo-terminal and the start symbol. Rules can't be empty.
+pub fn parse_grammar<'g>(
+    text: &'g str,
+) -> Result<AugmentedGrammar<TerminalName<'g>, NonTerminalName<'g>>, GrammarError> {
+    let mut lexer = GrammarLexer {
+        text,
+        chars: text.char_indices().peekable(),
+    };
+    let mut pieces = vec![];
+    while let Some(piece) = lexer.next()? {
+        pieces.push(piece);
+    }
+    let end = text.len();
+
+    // The nonterminals are known before the rules are read, so they are found first.
+    let mut nonterminals = BTreeSet::new();
+    let mut rule_starts = BTreeSet::new();
+    for window in pieces.windows(2) {
+        if let [(offset, Piece::Name(name)), (_, Piece::Arrow)] = window {
+            if RESERVED.contains(name) {
+                return Err(lexer.error(*offset, format!("`{}` is reserved", name)));
+            }
+            nonterminals.insert(*name);
+            rule_starts.insert(*offset);
+        }
+    }
+    let symbol = |offset: usize, name: &'g str| match name {
+        "error" => Ok(TerminalOrNonTerminal::Terminal(TerminalName::error())),
+        name if RESERVED.contains(&name) => {
+            Err(lexer.error(offset, format!("`{}` is reserved", name)))
+        }
+        name if nonterminals.contains(name) => {
+            Ok(TerminalOrNonTerminal::NonTerminal(NonTerminalName(name)))
+        }
+        name => Ok(TerminalOrNonTerminal::Terminal(TerminalName(name))),
+    };
+    let nonterminal = |offset: usize, name: &'g str| {
+        if nonterminals.contains(name) {
+            Ok(NonTerminalName(name))
+        } else {
+            Err(lexer.error(offset, format!("`{}` has no rules", name)))
+        }
+    };
+
+    let mut start = None;
+    let mut entry_points = vec![];
+    let mut rules = vec![];
+    let mut pieces = pieces.into_iter().peekable();
+    while let Some((offset, piece)) = pieces.next() {
+        match piece {
+            Piece::Directive("start") => match pieces.next() {
+                Some((offset, Piece::Name(name))) => start = Some(nonterminal(offset, name)?),
+                _ => return Err(lexer.error(offset, "expected a nonterminal after `%start`")),
+            },
+            Piece::Directive("entry") => {
+                while let Some((offset, Piece::Name(name))) = pieces.next_if(|(offset, piece)| {
+                    matches!(piece, Piece::Name(_)) && !rule_starts.contains(offset)
+                }) {
+                    entry_points.push(nonterminal(offset, name)?);
+                }
+            }
+            Piece::Directive(name) => {
+                return Err(lexer.error(offset, format!("unknown directive `%{}`", name)))
+            }
+            Piece::Name(lhs) => {
+                if pieces
+                    .next_if(|(_, piece)| *piece == Piece::Arrow)
+                    .is_none()
+                {
+                    return Err(lexer.error(offset, format!("expected `->` after `{}`", lhs)));
+                }
+                let lhs = NonTerminalName(lhs);
+                let mut rhs = vec![];
+                loop {
+                    match pieces.next() {
+                        Some((offset, Piece::Name(name))) => rhs.push(symbol(offset, name)?),
+                        Some((offset, piece @ (Piece::Pipe | Piece::Semicolon))) => {
+                            if rhs.is_empty() {
+                                return Err(lexer.error(offset, "rules can't be empty"));
+                            }
+                            rules.push(Rule {
+                                lhs,
+                                rhs: std::mem::take(&mut rhs),
+                            });
+                            if piece == Piece::Semicolon {
+                                break;
+                            }
+                        }
+                        Some((offset, _)) => {
+                            return Err(lexer.error(offset, "expected a name, `|` or `;`"))
+                        }
+                        None => return Err(lexer.error(end, "expected `;` after the last rule")),
+                    }
+                }
+            }
+            _ => return Err(lexer.error(offset, "expected a rule or a directive")),
+        }
+    }
+    let Some(first) = rules.first() else {
+        return Err(lexer.error(end, "the grammar has no rules"));
+    };
+    let start = start.unwrap_or(first.lhs);
+    Ok(AugmentedGrammar {
+        start_rule: Rule {
+            lhs: NonTerminalName::start(),
+            rhs: vec![TerminalOrNonTerminal::NonTerminal(start)],
+        },
+        rules,
+        entry_points,
+    })
+}
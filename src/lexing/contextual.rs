@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::parser_types::{
+    action_goto::{ParseOutput, ParsingTable},
+    nonterminal::NonTerminalTrait,
+    parser::{Parser, Status},
+    terminal::TerminalTrait,
+    token::Token,
+};
+
+use super::lexer::{Cursor, LexError, Lexer};
+
+/// Lexes with only the patterns of the terminals the parser can act on next, so tokens that
+/// clash are told apart by where they appear: a keyword is read as an identifier where only an
+/// identifier can follow, and `>>` as a `>` where `>>` can't. Skip patterns are always tried.
+///
+/// When no acceptable pattern matches, the token is read with every pattern instead, so the
+/// parser reports it as unexpected rather than the lexer failing on text that does form a token.
+pub struct ContextualLexer<
+    'a,
+    Terminal: std::fmt::Debug
+        + Serialize
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    lexer: &'a Lexer<Terminal>,
+    parsing_table: &'a ParsingTable<Terminal, NonTerminal>,
+    /// The patterns tried in every state, by pattern number.
+    allowed: BTreeMap<usize, Vec<bool>>,
+}
+
+impl<
+        'a,
+        Terminal: std::fmt::Debug
+            + Serialize
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + Serialize
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > ContextualLexer<'a, Terminal, NonTerminal>
+{
+    pub fn new(
+        lexer: &'a Lexer<Terminal>,
+        parsing_table: &'a ParsingTable<Terminal, NonTerminal>,
+    ) -> Self {
+        let patterns = lexer.patterns();
+        let mut allowed: BTreeMap<usize, Vec<bool>> = BTreeMap::new();
+        for (state, terminal, _) in parsing_table.action_cells() {
+            let row = allowed.entry(state).or_insert_with(|| {
+                (0..patterns)
+                    .map(|pattern| lexer.terminal(pattern).is_none())
+                    .collect()
+            });
+            for (pattern, allowed) in row.iter_mut().enumerate() {
+                if lexer.terminal(pattern) == Some(terminal) {
+                    *allowed = true;
+                }
+            }
+        }
+        Self {
+            lexer,
+            parsing_table,
+            allowed,
+        }
+    }
+    pub fn parsing_table(&self) -> &'a ParsingTable<Terminal, NonTerminal> {
+        self.parsing_table
+    }
+    /// Reads the token at `cursor` in `source` for a parser in `state` and moves `cursor` past
+    /// it, see [`Lexer::next_token`].
+    pub fn next_token(
+        &self,
+        source: &str,
+        cursor: &mut Cursor,
+        state: usize,
+    ) -> Result<Token<Terminal>, LexError> {
+        let row = self.allowed.get(&state);
+        self.lexer
+            .next_token(source, cursor, |pattern| {
+                row.is_some_and(|row| row[pattern])
+            })
+            .or_else(|_| self.lexer.next_token(source, cursor, |_| true))
+    }
+    /// Lexes and parses `source` together, asking `parser` for its state before every token.
+    /// Tokens are pushed one at a time, so errors are recovered from with the `error` rules only,
+    /// never repaired. Fails on text no pattern matches.
+    pub fn parse(
+        &self,
+        parser: &mut Parser<'_, Terminal, NonTerminal>,
+        source: &str,
+    ) -> Result<ParseOutput<Terminal, NonTerminal>, LexError> {
+        let mut output = ParseOutput {
+            tree: None,
+            errors: vec![],
+            repairs: vec![],
+        };
+        let mut cursor = Cursor::default();
+        loop {
+            let token = self.next_token(source, &mut cursor, parser.state())?;
+            let status = parser.push(token);
+            output.errors.extend(parser.take_errors());
+            match status {
+                Status::NeedMore => {}
+                Status::Accepted(tree) => {
+                    output.tree = Some(tree);
+                    return Ok(output);
+                }
+                Status::Error(err) => {
+                    output.errors.push(err);
+                    return Ok(output);
+                }
+            }
+        }
+    }
+}
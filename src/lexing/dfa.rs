@@ -0,0 +1,271 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::regex::{next_char, Regex};
+
+/// A Thompson automaton: every state has empty moves and moves on sets of characters, given as
+/// ranges of character class numbers.
+#[derive(Default)]
+struct Nfa {
+    empty: Vec<Vec<usize>>,
+    moves: Vec<Vec<(usize, usize, usize)>>,
+}
+
+impl Nfa {
+    fn state(&mut self) -> usize {
+        self.empty.push(vec![]);
+        self.moves.push(vec![]);
+        self.empty.len() - 1
+    }
+    /// Adds the states matching `regex` from `from`, returning the state it ends in.
+    fn add(&mut self, regex: &Regex, from: usize, classes: &[char]) -> usize {
+        match regex {
+            Regex::Empty => from,
+            Regex::Set(ranges) => {
+                let to = self.state();
+                for (lo, hi) in ranges {
+                    let first = classes.partition_point(|start| start <= lo) - 1;
+                    let last = classes.partition_point(|start| start <= hi) - 1;
+                    self.moves[from].push((first, last, to));
+                }
+                to
+            }
+            Regex::Concat(regexes) => regexes
+                .iter()
+                .fold(from, |from, regex| self.add(regex, from, classes)),
+            Regex::Alt(regexes) => {
+                let to = self.state();
+                for regex in regexes {
+                    let start = self.state();
+                    self.empty[from].push(start);
+                    let end = self.add(regex, start, classes);
+                    self.empty[end].push(to);
+                }
+                to
+            }
+            Regex::Repeat { regex, min, max } => {
+                let mut from = from;
+                for _ in 0..*min {
+                    from = self.add(regex, from, classes);
+                }
+                match max {
+                    None => {
+                        let start = self.state();
+                        self.empty[from].push(start);
+                        let end = self.add(regex, start, classes);
+                        self.empty[end].push(start);
+                        start
+                    }
+                    Some(max) => {
+                        for _ in *min..*max {
+                            // The end of the body may loop back into it, so skipping the body
+                            // goes to a state of its own.
+                            let start = self.state();
+                            self.empty[from].push(start);
+                            let end = self.add(regex, start, classes);
+                            let join = self.state();
+                            self.empty[from].push(join);
+                            self.empty[end].push(join);
+                            from = join;
+                        }
+                        from
+                    }
+                }
+            }
+        }
+    }
+    fn closure(&self, states: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+        let mut res = BTreeSet::new();
+        let mut stack: Vec<usize> = states.into_iter().collect();
+        while let Some(state) = stack.pop() {
+            if res.insert(state) {
+                stack.extend(&self.empty[state]);
+            }
+        }
+        res
+    }
+}
+
+fn collect_ranges(regex: &Regex, ranges: &mut Vec<(char, char)>) {
+    match regex {
+        Regex::Empty => {}
+        Regex::Set(set) => ranges.extend_from_slice(set),
+        Regex::Concat(regexes) | Regex::Alt(regexes) => {
+            for regex in regexes {
+                collect_ranges(regex, ranges);
+            }
+        }
+        Regex::Repeat { regex, .. } => collect_ranges(regex, ranges),
+    }
+}
+
+/// A deterministic automaton matching several patterns at once. Characters are grouped into
+/// classes that no pattern tells apart, so the transition table has a column per class rather
+/// than per character. State 0 is the dead state, which never accepts and is never left, and
+/// state 1 the start state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dfa {
+    /// The first character of every class, in increasing order.
+    classes: Vec<char>,
+    /// The next state of every state on every class, row by row.
+    transitions: Vec<usize>,
+    /// The patterns every state accepts, best first.
+    accepting: Vec<Vec<usize>>,
+}
+
+impl Dfa {
+    pub const DEAD: usize = 0;
+    pub const START: usize = 1;
+
+    /// Builds the automaton of `patterns`, given with their priorities. When a string matches
+    /// several patterns the one with the highest priority is preferred, and among those the first
+    /// one.
+    pub fn new(patterns: &[(Regex, i32)]) -> Self {
+        let mut ranges = vec![];
+        for (regex, _) in patterns {
+            collect_ranges(regex, &mut ranges);
+        }
+        let mut starts = BTreeSet::from(['\0']);
+        for (lo, hi) in ranges {
+            starts.insert(lo);
+            starts.extend(next_char(hi));
+        }
+        let classes: Vec<char> = starts.into_iter().collect();
+
+        let mut nfa = Nfa::default();
+        let start = nfa.state();
+        let mut accepts = BTreeMap::new();
+        for (index, (regex, _)) in patterns.iter().enumerate() {
+            let from = nfa.state();
+            nfa.empty[start].push(from);
+            let to = nfa.add(regex, from, &classes);
+            // Every pattern starts from a state of its own, so it ends in one of its own too.
+            accepts.insert(to, index);
+        }
+        let accepted = |states: &BTreeSet<usize>| {
+            let mut res: Vec<usize> = states
+                .iter()
+                .filter_map(|state| accepts.get(state).copied())
+                .collect();
+            res.sort_by_key(|index| (-patterns[*index].1, *index));
+            res
+        };
+
+        let mut sets = vec![BTreeSet::new(), nfa.closure([start])];
+        let mut ids = BTreeMap::from([(sets[0].clone(), 0), (sets[1].clone(), 1)]);
+        let mut transitions = vec![];
+        let mut next = 0;
+        while next < sets.len() {
+            let mut targets: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+            for state in &sets[next] {
+                for (first, last, to) in &nfa.moves[*state] {
+                    for class in *first..=*last {
+                        targets.entry(class).or_default().push(*to);
+                    }
+                }
+            }
+            let mut row = vec![Self::DEAD; classes.len()];
+            for (class, to) in targets {
+                let set = nfa.closure(to);
+                row[class] = *ids.entry(set.clone()).or_insert_with(|| {
+                    sets.push(set);
+                    sets.len() - 1
+                });
+            }
+            transitions.extend(row);
+            next += 1;
+        }
+        let accepting = sets.iter().map(accepted).collect();
+        Self {
+            classes,
+            transitions,
+            accepting,
+        }
+    }
+    pub fn states(&self) -> usize {
+        self.accepting.len()
+    }
+    /// The number of the class of `c`.
+    pub fn class(&self, c: char) -> usize {
+        self.classes.partition_point(|start| *start <= c) - 1
+    }
+    pub fn next(&self, state: usize, c: char) -> usize {
+        self.transitions[state * self.classes.len() + self.class(c)]
+    }
+    /// The patterns `state` accepts, best first.
+    pub fn accepting(&self, state: usize) -> &[usize] {
+        &self.accepting[state]
+    }
+    /// The length in bytes of the longest prefix of `text` matching a pattern `allowed` returns
+    /// `true` for, with the best such pattern. An empty prefix is never matched.
+    pub fn longest_match(
+        &self,
+        text: &str,
+        allowed: impl Fn(usize) -> bool,
+    ) -> Option<(usize, usize)> {
+        let mut state = Self::START;
+        let mut res = None;
+        for (offset, c) in text.char_indices() {
+            state = self.next(state, c);
+            if state == Self::DEAD {
+                break;
+            }
+            if let Some(pattern) = self.accepting[state].iter().find(|p| allowed(**p)) {
+                res = Some((offset + c.len_utf8(), *pattern));
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dfa;
+    use crate::lexing::regex::Regex;
+
+    /// Whether the whole of `text` matches `pattern`.
+    fn matches(pattern: &str, text: &str) -> bool {
+        let dfa = Dfa::new(&[(Regex::parse(pattern).unwrap(), 0)]);
+        let state = text.chars().fold(Dfa::START, |state, c| dfa.next(state, c));
+        !dfa.accepting(state).is_empty()
+    }
+
+    #[test]
+    fn optional() {
+        assert!(matches("ab?c", "ac"));
+        assert!(matches("ab?c", "abc"));
+        assert!(!matches("ab?c", "abbc"));
+    }
+
+    #[test]
+    fn optional_loop() {
+        assert!(matches("(ab*)?c", "c"));
+        assert!(matches("(ab*)?c", "ac"));
+        assert!(matches("(ab*)?c", "abbc"));
+        assert!(!matches("(ab*)?c", "bc"));
+        assert!(!matches("(ab+)?c", "bc"));
+        assert!(!matches("(ab+)?c", "ac"));
+    }
+
+    #[test]
+    fn bounded() {
+        assert!(!matches("a{2,3}", "a"));
+        assert!(matches("a{2,3}", "aa"));
+        assert!(matches("a{2,3}", "aaa"));
+        assert!(!matches("a{2,3}", "aaaa"));
+        assert!(matches("(ab*){1,2}", "abbab"));
+        assert!(!matches("(ab*){0,2}", "b"));
+        assert!(!matches("(ab*){0,2}", "ababab"));
+    }
+
+    #[test]
+    fn nested() {
+        assert!(matches("((ab)?c)*", ""));
+        assert!(matches("((ab)?c)*", "cabcc"));
+        assert!(!matches("((ab)?c)*", "bc"));
+        assert!(matches("(a(b{1,2})?){2}", "abbab"));
+        assert!(!matches("(a(b{1,2})?){2}", "abbb"));
+        assert!(!matches("(a(b*)?)?c", "bc"));
+    }
+}
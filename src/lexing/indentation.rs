@@ -0,0 +1,301 @@
+use std::collections::VecDeque;
+
+use crate::parser_types::{
+    terminal::TerminalTrait,
+    token::{Position, Span, Token},
+};
+
+/// What line breaks between brackets do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InBrackets {
+    /// Nothing: the lines are joined, as in Python.
+    #[default]
+    Join,
+    /// They end a line, but indentation is not tracked.
+    Newline,
+    /// Brackets make no difference.
+    Layout,
+}
+
+/// An item of a token stream: a token, or the result of lexing one, as from
+/// [`crate::lexing::lexer::Lexer::tokens`].
+pub trait TokenItem<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+>
+{
+    /// The token, or `None` for an error.
+    fn token(&self) -> Option<&Token<Terminal>>;
+    fn from_token(token: Token<Terminal>) -> Self;
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+    > TokenItem<Terminal> for Token<Terminal>
+{
+    fn token(&self) -> Option<&Token<Terminal>> {
+        Some(self)
+    }
+    fn from_token(token: Token<Terminal>) -> Self {
+        token
+    }
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        E,
+    > TokenItem<Terminal> for Result<Token<Terminal>, E>
+{
+    fn token(&self) -> Option<&Token<Terminal>> {
+        self.as_ref().ok()
+    }
+    fn from_token(token: Token<Terminal>) -> Self {
+        Ok(token)
+    }
+}
+
+/// Adds the tokens of an indentation-sensitive language to a token stream: a `newline` token at
+/// the end of every line, an `indent` token where a line is indented more than the one before,
+/// and a `dedent` token for every level a line goes back. At the end of the input the last line
+/// is ended and every level still open is closed.
+///
+/// Lines and indentation are read from the positions of the tokens, so blank lines and lines
+/// holding only skipped text don't count, a token spanning several lines continues its line, and
+/// a tab counts as a single column. The first line sets the outermost level. A line that goes
+/// back to a column no enclosing line starts at gets an error token before it instead of a
+/// `dedent`, and its column becomes the level of the lines after it.
+///
+/// The stream may be of tokens or of lexing results, which are read the same way and yielded as
+/// they come, errors included. Errors take no part in the layout.
+pub struct Indentation<
+    I: Iterator<Item = Item>,
+    Item: TokenItem<Terminal>,
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+> {
+    tokens: I,
+    newline: Terminal,
+    indent: Terminal,
+    dedent: Terminal,
+    brackets: Vec<(Terminal, Terminal)>,
+    in_brackets: InBrackets,
+    /// The columns of the open indentation levels, innermost last.
+    levels: Vec<usize>,
+    /// The number of brackets open.
+    depth: usize,
+    /// The end of the last token read, if any.
+    end: Option<Position>,
+    pending: VecDeque<Item>,
+    done: bool,
+}
+
+impl<
+        I: Iterator<Item = Item>,
+        Item: TokenItem<Terminal>,
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+    > Indentation<I, Item, Terminal>
+{
+    pub fn new(
+        tokens: impl IntoIterator<IntoIter = I>,
+        newline: Terminal,
+        indent: Terminal,
+        dedent: Terminal,
+    ) -> Self {
+        Self {
+            tokens: tokens.into_iter(),
+            newline,
+            indent,
+            dedent,
+            brackets: vec![],
+            in_brackets: InBrackets::default(),
+            levels: vec![1],
+            depth: 0,
+            end: None,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+    /// Treats `open` and `close` as brackets, see [`InBrackets`].
+    pub fn with_brackets(mut self, open: Terminal, close: Terminal) -> Self {
+        self.brackets.push((open, close));
+        self
+    }
+    pub fn with_in_brackets(mut self, in_brackets: InBrackets) -> Self {
+        self.in_brackets = in_brackets;
+        self
+    }
+    fn synthetic(&mut self, kind: Terminal, at: Position) {
+        self.pending
+            .push_back(Item::from_token(Token::new(kind, "", Span::empty(at))));
+    }
+    /// Queues the tokens that go before a token starting a new line at `at`.
+    fn line_break(&mut self, end: Position, at: Position) {
+        let layout = self.depth == 0 || self.in_brackets == InBrackets::Layout;
+        if !layout && self.in_brackets == InBrackets::Join {
+            return;
+        }
+        self.synthetic(self.newline, end);
+        if !layout {
+            return;
+        }
+        let column = at.column;
+        if column > *self.levels.last().unwrap() {
+            self.levels.push(column);
+            self.synthetic(self.indent, at);
+            return;
+        }
+        while column < *self.levels.last().unwrap() {
+            self.levels.pop();
+            if column > *self.levels.last().unwrap() {
+                // The line stops between two levels. It takes the place of the level it went back
+                // from, so the lines after it that line up with it don't open a new one.
+                self.levels.push(column);
+                self.synthetic(Terminal::error(), at);
+                return;
+            }
+            self.synthetic(self.dedent, at);
+        }
+    }
+    /// Queues the tokens that close the input before `eof`, the end-of-input token at `at`.
+    fn finish(&mut self, eof: Item, at: Position) {
+        if let Some(end) = self.end {
+            self.synthetic(self.newline, end);
+        }
+        while self.levels.len() > 1 {
+            self.levels.pop();
+            self.synthetic(self.dedent, at);
+        }
+        self.pending.push_back(eof);
+        self.done = true;
+    }
+}
+
+impl<
+        I: Iterator<Item = Item>,
+        Item: TokenItem<Terminal>,
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+    > Iterator for Indentation<I, Item, Terminal>
+{
+    type Item = Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.done {
+            let Some(item) = self.tokens.next() else {
+                let at = self.end.unwrap_or_default();
+                self.finish(Item::from_token(Token::eof(at)), at);
+                break;
+            };
+            let Some(token) = item.token() else {
+                self.pending.push_back(item);
+                break;
+            };
+            if token.kind.is_eof() {
+                let at = token.span.start;
+                self.finish(item, at);
+                break;
+            }
+            let (kind, span) = (token.kind, token.span);
+            match self.end {
+                None => self.levels = vec![span.start.column],
+                Some(end) if span.start.line > end.line => self.line_break(end, span.start),
+                Some(_) => {}
+            }
+            if self.brackets.iter().any(|(open, _)| *open == kind) {
+                self.depth += 1;
+            } else if self.brackets.iter().any(|(_, close)| *close == kind) {
+                self.depth = self.depth.saturating_sub(1);
+            }
+            self.end = Some(span.end);
+            self.pending.push_back(item);
+        }
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Indentation;
+    use crate::{
+        grammar_file::TerminalName,
+        parser_types::token::{Position, Span, Token},
+    };
+
+    /// The kinds of the tokens of `source` with indentation tokens added, every word a token.
+    fn kinds(source: &str) -> Vec<&'static str> {
+        let mut tokens = vec![];
+        let mut position = Position::default();
+        for line in source.split_inclusive('\n') {
+            for word in line.split_inclusive(' ') {
+                let start = position;
+                position = position.advance(word);
+                if !word.trim().is_empty() {
+                    let span = Span::new(start, start.advance(word.trim_end()));
+                    tokens.push(Token::new(TerminalName("word"), word.trim_end(), span));
+                }
+            }
+        }
+        let newline = TerminalName("newline");
+        let (indent, dedent) = (TerminalName("indent"), TerminalName("dedent"));
+        Indentation::new(tokens, newline, indent, dedent)
+            .map(|token: Token<TerminalName>| token.kind.0)
+            .collect()
+    }
+
+    #[test]
+    fn inconsistent_dedent() {
+        let source = "a\n    b\n  c\n  d\ne\n";
+        assert_eq!(
+            kinds(source),
+            [
+                "word", "newline", "indent", "word", "newline", "Error", "word", "newline", "word",
+                "newline", "dedent", "word", "newline", "EOF"
+            ]
+        );
+    }
+}
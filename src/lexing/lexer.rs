@@ -0,0 +1,629 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::parser_types::{
+    terminal::TerminalTrait,
+    token::{Position, Span, Token, Trivia},
+};
+
+use super::{
+    dfa::Dfa,
+    regex::{Regex, RegexError},
+};
+
+/// A character no token starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LexError {
+    pub position: Position,
+    pub character: char,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unexpected character {:?} at {}:{}",
+            self.character, self.position.line, self.position.column
+        )
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Why [`LexerBuilder::build`] failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    Regex(RegexError),
+    /// A pattern matches the empty string, so a lexer could loop forever on it.
+    EmptyMatch {
+        pattern: String,
+    },
+    /// A mode has no patterns, so no text could be read in it.
+    EmptyMode {
+        mode: String,
+    },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Regex(err) => write!(f, "{}", err),
+            BuildError::EmptyMatch { pattern } => {
+                write!(f, "pattern {:?} matches the empty string", pattern)
+            }
+            BuildError::EmptyMode { mode } => write!(f, "mode {:?} has no patterns", mode),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl From<RegexError> for BuildError {
+    fn from(err: RegexError) -> Self {
+        BuildError::Regex(err)
+    }
+}
+
+/// What reading a token does to the mode stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModeChange {
+    /// Enters the mode with this number until it is popped.
+    Push(usize),
+    /// Goes back to the mode the current one was pushed from. Popping the initial mode does
+    /// nothing.
+    Pop,
+}
+
+/// Which of the tokens around it the trivia between two tokens goes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Attachment {
+    /// All of it leads the next token.
+    #[default]
+    Leading,
+    /// All of it trails the previous token.
+    Trailing,
+    /// The trivia on the line of the previous token trails it, up to and including the line
+    /// break, and the rest leads the next token. Whitespace is split at the line break, other
+    /// trivia is kept whole.
+    LineEnd,
+}
+
+impl Attachment {
+    /// The number of pieces of `trivia` that trail a token ending on `line`. A piece of
+    /// whitespace holding the line break is split first.
+    fn split(&self, trivia: &mut Vec<Trivia>, line: usize) -> usize {
+        match self {
+            Attachment::Leading => 0,
+            Attachment::Trailing => trivia.len(),
+            Attachment::LineEnd => {
+                for (i, piece) in trivia.iter().enumerate() {
+                    if piece.span.start.line != line {
+                        return i;
+                    }
+                    if piece.span.end.line == line {
+                        continue;
+                    }
+                    let Some(newline) = piece.text.find('\n') else {
+                        return i + 1;
+                    };
+                    if !piece.text.trim().is_empty() || newline + 1 == piece.text.len() {
+                        return i + 1;
+                    }
+                    let (text, rest) = piece.text.split_at(newline + 1);
+                    let at = piece.span.start.advance(text);
+                    let rest = Trivia {
+                        text: rest.to_string(),
+                        span: Span::new(at, piece.span.end),
+                        pattern: piece.pattern,
+                    };
+                    let piece = &mut trivia[i];
+                    piece.text.truncate(newline + 1);
+                    piece.span.end = at;
+                    trivia.insert(i + 1, rest);
+                    return i + 1;
+                }
+                trivia.len()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Definition<Terminal> {
+    /// The pattern as written, for error messages.
+    source: String,
+    regex: Result<Regex, RegexError>,
+    /// `None` for skip patterns.
+    terminal: Option<Terminal>,
+    priority: i32,
+    mode: usize,
+    change: Option<ModeChange>,
+}
+
+/// Collects the token definitions of a [`Lexer`]. Every definition is a pattern producing a
+/// terminal, or nothing for skip patterns such as whitespace and comments. When several patterns
+/// match at the same place the longest match wins, then the pattern with the highest priority,
+/// then the one defined first, so keywords defined before the identifier pattern take precedence
+/// over it.
+///
+/// Definitions belong to a mode, like the start conditions of flex, and only those of the mode on
+/// top of the mode stack are tried. Definitions go to [`LexerBuilder::INITIAL`] until
+/// [`LexerBuilder::in_mode`] is called, and a definition can push or pop a mode once its text is
+/// read, so string interpolation or an embedded language can have patterns of its own while
+/// the parser still gets a single stream of tokens.
+#[derive(Debug, Clone)]
+pub struct LexerBuilder<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+> {
+    definitions: Vec<Definition<Terminal>>,
+    modes: Vec<String>,
+    /// The mode new definitions go to.
+    mode: usize,
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+    > Default for LexerBuilder<Terminal>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+    > LexerBuilder<Terminal>
+{
+    /// The mode lexing starts in.
+    pub const INITIAL: &'static str = "INITIAL";
+
+    pub fn new() -> Self {
+        Self {
+            definitions: vec![],
+            modes: vec![Self::INITIAL.to_string()],
+            mode: 0,
+        }
+    }
+    /// Produces `terminal` for text matching the regular expression `pattern`, in the syntax of
+    /// [`Regex::parse`].
+    pub fn with_token(self, terminal: Terminal, pattern: &str) -> Self {
+        self.with_token_priority(terminal, pattern, 0)
+    }
+    pub fn with_token_priority(self, terminal: Terminal, pattern: &str, priority: i32) -> Self {
+        self.define(pattern, Regex::parse(pattern), Some(terminal), priority)
+    }
+    /// Produces `terminal` for `text` itself, with no characters treated specially.
+    pub fn with_literal(self, terminal: Terminal, text: &str) -> Self {
+        self.define(text, Ok(Regex::literal(text)), Some(terminal), 0)
+    }
+    /// Skips text matching `pattern` between tokens.
+    pub fn with_skip(self, pattern: &str) -> Self {
+        self.define(pattern, Regex::parse(pattern), None, 0)
+    }
+    fn define(
+        mut self,
+        source: &str,
+        regex: Result<Regex, RegexError>,
+        terminal: Option<Terminal>,
+        priority: i32,
+    ) -> Self {
+        self.definitions.push(Definition {
+            source: source.to_string(),
+            regex,
+            terminal,
+            priority,
+            mode: self.mode,
+            change: None,
+        });
+        self
+    }
+    /// Adds the definitions that follow to `mode`.
+    pub fn in_mode(mut self, mode: &str) -> Self {
+        self.mode = self.mode_number(mode);
+        self
+    }
+    /// Makes the last definition enter `mode` after its text is read.
+    pub fn with_push(mut self, mode: &str) -> Self {
+        let mode = self.mode_number(mode);
+        self.change(ModeChange::Push(mode))
+    }
+    /// Makes the last definition leave the current mode after its text is read.
+    pub fn with_pop(self) -> Self {
+        self.change(ModeChange::Pop)
+    }
+    fn change(mut self, change: ModeChange) -> Self {
+        self.definitions
+            .last_mut()
+            .expect("a mode change follows the definition it belongs to")
+            .change = Some(change);
+        self
+    }
+    fn mode_number(&mut self, mode: &str) -> usize {
+        match self.modes.iter().position(|m| m == mode) {
+            Some(n) => n,
+            None => {
+                self.modes.push(mode.to_string());
+                self.modes.len() - 1
+            }
+        }
+    }
+    /// Compiles the definitions of every mode to a DFA. Fails on the first pattern that doesn't
+    /// parse or that matches the empty string, or on a mode without patterns.
+    pub fn build(self) -> Result<Lexer<Terminal>, BuildError> {
+        let mut modes = vec![];
+        for (mode, name) in self.modes.iter().enumerate() {
+            let mut patterns = vec![];
+            let mut regexes = vec![];
+            for (pattern, definition) in self.definitions.iter().enumerate() {
+                if definition.mode == mode {
+                    patterns.push(pattern);
+                    regexes.push((definition.regex.clone()?, definition.priority));
+                }
+            }
+            if patterns.is_empty() {
+                return Err(BuildError::EmptyMode { mode: name.clone() });
+            }
+            let dfa = Dfa::new(&regexes);
+            if let Some(pattern) = dfa.accepting(Dfa::START).first() {
+                return Err(BuildError::EmptyMatch {
+                    pattern: self.definitions[patterns[*pattern]].source.clone(),
+                });
+            }
+            modes.push(Mode {
+                name: name.clone(),
+                dfa,
+                patterns,
+            });
+        }
+        let terminals = self.definitions.iter().map(|d| d.terminal).collect();
+        let changes = self.definitions.iter().map(|d| d.change).collect();
+        Ok(Lexer {
+            modes,
+            terminals,
+            changes,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Mode {
+    name: String,
+    dfa: Dfa,
+    /// The number of every pattern of the mode, by its number in `dfa`.
+    patterns: Vec<usize>,
+}
+
+/// Where a lexer is in its input: the position of the next token and the stack of modes, the
+/// current one last.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Cursor {
+    pub position: Position,
+    modes: Vec<usize>,
+}
+
+impl Cursor {
+    pub fn new(position: Position) -> Self {
+        Self {
+            position,
+            modes: vec![],
+        }
+    }
+    /// The number of the current mode, 0 for the initial mode.
+    pub fn mode(&self) -> usize {
+        self.modes.last().copied().unwrap_or(0)
+    }
+    /// The modes pushed and not popped yet, the current one last. The initial mode is not
+    /// included.
+    pub fn modes(&self) -> &[usize] {
+        &self.modes
+    }
+    fn apply(&mut self, change: ModeChange) {
+        match change {
+            ModeChange::Push(mode) => self.modes.push(mode),
+            ModeChange::Pop => {
+                self.modes.pop();
+            }
+        }
+    }
+}
+
+/// Turns source text into the tokens [`crate::parser_types::parser::Parser::parse`] reads. Build
+/// one with [`LexerBuilder`]. A lexer serializes with its DFAs, so it can be built once, for
+/// instance by a build script, and loaded at run time.
+///
+/// Patterns are numbered in the order they were defined, across all modes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lexer<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+> {
+    modes: Vec<Mode>,
+    /// The terminal of every pattern, `None` for skip patterns.
+    terminals: Vec<Option<Terminal>>,
+    changes: Vec<Option<ModeChange>>,
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+    > Lexer<Terminal>
+{
+    pub fn builder() -> LexerBuilder<Terminal> {
+        LexerBuilder::new()
+    }
+    /// The automaton of `mode`. Its pattern numbers are those of the mode, see
+    /// [`Lexer::mode_patterns`].
+    pub fn dfa(&self, mode: usize) -> &Dfa {
+        &self.modes[mode].dfa
+    }
+    /// The number of the mode called `name`.
+    pub fn mode(&self, name: &str) -> Option<usize> {
+        self.modes.iter().position(|mode| mode.name == name)
+    }
+    pub fn mode_name(&self, mode: usize) -> &str {
+        &self.modes[mode].name
+    }
+    /// The patterns of `mode`, in the order they were defined.
+    pub fn mode_patterns(&self, mode: usize) -> &[usize] {
+        &self.modes[mode].patterns
+    }
+    /// The number of patterns.
+    pub fn patterns(&self) -> usize {
+        self.terminals.len()
+    }
+    /// The terminal pattern number `pattern` produces, `None` for skip patterns.
+    pub fn terminal(&self, pattern: usize) -> Option<Terminal> {
+        self.terminals[pattern]
+    }
+    pub fn mode_change(&self, pattern: usize) -> Option<ModeChange> {
+        self.changes[pattern]
+    }
+    /// The tokens of `source`, ending with an end-of-input token. A character no token starts
+    /// with gives an error and is skipped, so lexing can go on after it.
+    pub fn tokens<'l, 's>(&'l self, source: &'s str) -> Tokens<'l, 's, Terminal> {
+        Tokens {
+            lexer: self,
+            source,
+            cursor: Cursor::default(),
+            done: false,
+            attachment: None,
+            ahead: None,
+        }
+    }
+    /// Reads the token at `cursor` in `source` and moves `cursor` past it, skipping the text
+    /// skip patterns match first. Only the patterns of the current mode that `allowed` returns
+    /// `true` for are tried, skip patterns included. At the end of `source` this is the
+    /// end-of-input token. On an error `cursor` is left at the offending character.
+    pub fn next_token(
+        &self,
+        source: &str,
+        cursor: &mut Cursor,
+        allowed: impl Fn(usize) -> bool,
+    ) -> Result<Token<Terminal>, LexError> {
+        self.read(source, cursor, allowed, None)
+    }
+    /// Like [`Lexer::next_token`], keeping the skipped text in `trivia`.
+    pub fn next_token_with_trivia(
+        &self,
+        source: &str,
+        cursor: &mut Cursor,
+        allowed: impl Fn(usize) -> bool,
+        trivia: &mut Vec<Trivia>,
+    ) -> Result<Token<Terminal>, LexError> {
+        self.read(source, cursor, allowed, Some(trivia))
+    }
+    fn read(
+        &self,
+        source: &str,
+        cursor: &mut Cursor,
+        allowed: impl Fn(usize) -> bool,
+        mut trivia: Option<&mut Vec<Trivia>>,
+    ) -> Result<Token<Terminal>, LexError> {
+        loop {
+            let rest = &source[cursor.position.offset..];
+            let start = cursor.position;
+            let Some(c) = rest.chars().next() else {
+                return Ok(Token::eof(start));
+            };
+            let mode = &self.modes[cursor.mode()];
+            let Some((len, pattern)) = mode
+                .dfa
+                .longest_match(rest, |pattern| allowed(mode.patterns[pattern]))
+            else {
+                return Err(LexError {
+                    position: start,
+                    character: c,
+                });
+            };
+            let pattern = mode.patterns[pattern];
+            let text = &rest[..len];
+            cursor.position = start.advance(text);
+            if let Some(change) = self.changes[pattern] {
+                cursor.apply(change);
+            }
+            let span = Span::new(start, cursor.position);
+            match self.terminals[pattern] {
+                Some(terminal) => return Ok(Token::new(terminal, text, span)),
+                None => {
+                    if let Some(trivia) = trivia.as_deref_mut() {
+                        trivia.push(Trivia {
+                            text: text.to_string(),
+                            span,
+                            pattern,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    /// Every token of `source`, or the first error.
+    pub fn tokenize(&self, source: &str) -> Result<Vec<Token<Terminal>>, LexError> {
+        self.tokens(source).collect()
+    }
+}
+
+/// The iterator returned by [`Lexer::tokens`].
+#[derive(Debug, Clone)]
+pub struct Tokens<
+    'l,
+    's,
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+> {
+    lexer: &'l Lexer<Terminal>,
+    source: &'s str,
+    cursor: Cursor,
+    done: bool,
+    attachment: Option<Attachment>,
+    /// The token after the last one returned, read to split the trivia between them.
+    ahead: Option<Result<Token<Terminal>, LexError>>,
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+    > Tokens<'_, '_, Terminal>
+{
+    /// Keeps the skipped text as the trivia of the tokens, split between the tokens around it
+    /// by `attachment`. The trivia at the end of the input trails the last token, so that
+    /// printing the tokens with their trivia gives back `source` exactly, provided it has no
+    /// lexical errors. The cursor is then one token ahead of the tokens returned.
+    pub fn with_trivia(mut self, attachment: Attachment) -> Self {
+        self.attachment = Some(attachment);
+        self
+    }
+    /// Where the next token is looked for, and in which modes.
+    pub fn cursor(&self) -> &Cursor {
+        &self.cursor
+    }
+    fn read(&mut self, trivia: &mut Vec<Trivia>) -> Result<Token<Terminal>, LexError> {
+        let res =
+            self.lexer
+                .next_token_with_trivia(self.source, &mut self.cursor, |_| true, trivia);
+        if let Err(err) = &res {
+            self.skip_char(err);
+        }
+        res
+    }
+    /// Moves past the character of `err`, so lexing can go on after it.
+    fn skip_char(&mut self, err: &LexError) {
+        let mut buffer = [0; 4];
+        let text = err.character.encode_utf8(&mut buffer);
+        self.cursor.position = err.position.advance(text);
+    }
+    fn next_with_trivia(&mut self, attachment: Attachment) -> Result<Token<Terminal>, LexError> {
+        let mut token = match self.ahead.take() {
+            Some(token) => token?,
+            None => {
+                let mut leading = vec![];
+                let mut token = self.read(&mut leading)?;
+                token.trivia_mut().leading = leading;
+                token
+            }
+        };
+        if token.kind.is_eof() {
+            self.done = true;
+            return Ok(token);
+        }
+        let mut trivia = vec![];
+        let next = self.read(&mut trivia);
+        match next {
+            Ok(mut next) if !next.kind.is_eof() => {
+                let at = attachment.split(&mut trivia, token.span.end.line);
+                next.trivia_mut().leading = trivia.split_off(at);
+                token.trivia_mut().trailing = trivia;
+                self.ahead = Some(Ok(next));
+            }
+            next => {
+                token.trivia_mut().trailing = trivia;
+                self.ahead = Some(next);
+            }
+        }
+        Ok(token)
+    }
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+    > Iterator for Tokens<'_, '_, Terminal>
+{
+    type Item = Result<Token<Terminal>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Some(attachment) = self.attachment {
+            return Some(self.next_with_trivia(attachment));
+        }
+        let res = self
+            .lexer
+            .next_token(self.source, &mut self.cursor, |_| true);
+        match &res {
+            Ok(token) => self.done = token.kind.is_eof(),
+            Err(err) => self.skip_char(err),
+        }
+        Some(res)
+    }
+}
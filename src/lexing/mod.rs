@@ -0,0 +1,5 @@
+pub mod contextual;
+pub mod dfa;
+pub mod indentation;
+pub mod lexer;
+pub mod regex;
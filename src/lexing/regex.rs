@@ -0,0 +1,332 @@
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+/// A regular expression over characters. Character sets are lists of inclusive ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Regex {
+    /// Matches the empty string.
+    Empty,
+    Set(Vec<(char, char)>),
+    Concat(Vec<Regex>),
+    Alt(Vec<Regex>),
+    /// `min` or more repetitions, at most `max` if given.
+    Repeat {
+        regex: Box<Regex>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// A pattern that could not be parsed, with the byte offset in the pattern where parsing failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexError {
+    pub pattern: String,
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid pattern {:?} at offset {}: {}",
+            self.pattern, self.offset, self.message
+        )
+    }
+}
+
+impl std::error::Error for RegexError {}
+
+impl Regex {
+    /// Parses `pattern`. The syntax is the common subset of the usual regex dialects:
+    /// alternation `|`, groups `(...)` and `(?:...)`, the quantifiers `*`, `+`, `?`, `{m}`,
+    /// `{m,}` and `{m,n}`, sets `[...]` and `[^...]` with ranges, `.` for any character but a
+    /// newline, the classes `\d`, `\w`, `\s` and their negations, the escapes `\n`, `\r`, `\t`,
+    /// `\f`, `\v`, `\0`, `\xHH` and `\u{H...}`, and `\` before any other character to match it
+    /// literally. There are no anchors, lookarounds or backreferences.
+    pub fn parse(pattern: &str) -> Result<Self, RegexError> {
+        let mut parser = RegexParser {
+            pattern,
+            chars: pattern.char_indices().peekable(),
+        };
+        let regex = parser.alternation()?;
+        match parser.chars.next() {
+            None => Ok(regex),
+            Some((offset, _)) => Err(parser.error(offset, "unmatched `)`")),
+        }
+    }
+    /// Matches `text` literally.
+    pub fn literal(text: &str) -> Self {
+        Regex::Concat(text.chars().map(|c| Regex::Set(vec![(c, c)])).collect())
+    }
+}
+
+struct RegexParser<'p> {
+    pattern: &'p str,
+    chars: Peekable<CharIndices<'p>>,
+}
+
+impl RegexParser<'_> {
+    fn error(&self, offset: usize, message: impl Into<String>) -> RegexError {
+        RegexError {
+            pattern: self.pattern.to_string(),
+            offset,
+            message: message.into(),
+        }
+    }
+    fn offset(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map_or(self.pattern.len(), |(offset, _)| *offset)
+    }
+    fn eat(&mut self, c: char) -> bool {
+        self.chars.next_if(|(_, next)| *next == c).is_some()
+    }
+    fn alternation(&mut self) -> Result<Regex, RegexError> {
+        let mut alternatives = vec![self.concatenation()?];
+        while self.eat('|') {
+            alternatives.push(self.concatenation()?);
+        }
+        Ok(match alternatives.len() {
+            1 => alternatives.pop().unwrap(),
+            _ => Regex::Alt(alternatives),
+        })
+    }
+    fn concatenation(&mut self) -> Result<Regex, RegexError> {
+        let mut items = vec![];
+        while let Some(&(_, c)) = self.chars.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            items.push(self.quantifiers(atom)?);
+        }
+        Ok(match items.len() {
+            0 => Regex::Empty,
+            1 => items.pop().unwrap(),
+            _ => Regex::Concat(items),
+        })
+    }
+    fn quantifiers(&mut self, mut regex: Regex) -> Result<Regex, RegexError> {
+        loop {
+            let offset = self.offset();
+            let (min, max) = if self.eat('*') {
+                (0, None)
+            } else if self.eat('+') {
+                (1, None)
+            } else if self.eat('?') {
+                (0, Some(1))
+            } else if self.eat('{') {
+                let min = self.number()?;
+                let max = if self.eat(',') {
+                    match self.chars.peek() {
+                        Some((_, '}')) => None,
+                        _ => Some(self.number()?),
+                    }
+                } else {
+                    Some(min)
+                };
+                if !self.eat('}') {
+                    let offset = self.offset();
+                    return Err(self.error(offset, "expected `}`"));
+                }
+                if max.is_some_and(|max| max < min) {
+                    return Err(self.error(offset, "repetition bounds out of order"));
+                }
+                (min, max)
+            } else {
+                return Ok(regex);
+            };
+            regex = Regex::Repeat {
+                regex: Box::new(regex),
+                min,
+                max,
+            };
+        }
+    }
+    fn number(&mut self) -> Result<usize, RegexError> {
+        let offset = self.offset();
+        let mut digits = String::new();
+        while let Some((_, c)) = self.chars.next_if(|(_, c)| c.is_ascii_digit()) {
+            digits.push(c);
+        }
+        digits
+            .parse()
+            .map_err(|_| self.error(offset, "expected a number"))
+    }
+    fn atom(&mut self) -> Result<Regex, RegexError> {
+        let (offset, c) = self.chars.next().unwrap();
+        match c {
+            '(' => {
+                if self.eat('?') && !self.eat(':') {
+                    return Err(self.error(offset, "only `(?:` groups are supported"));
+                }
+                let regex = self.alternation()?;
+                if !self.eat(')') {
+                    let offset = self.offset();
+                    return Err(self.error(offset, "expected `)`"));
+                }
+                Ok(regex)
+            }
+            '[' => self.set(),
+            '.' => Ok(Regex::Set(complement(&[('\n', '\n')]))),
+            '\\' => Ok(Regex::Set(self.escape()?)),
+            '*' | '+' | '?' | '{' => Err(self.error(offset, "nothing to repeat")),
+            c => Ok(Regex::Set(vec![(c, c)])),
+        }
+    }
+    /// The set of a `[...]`, after the `[`.
+    fn set(&mut self) -> Result<Regex, RegexError> {
+        let negated = self.eat('^');
+        let mut ranges = vec![];
+        let mut first = true;
+        loop {
+            let Some((offset, c)) = self.chars.next() else {
+                return Err(self.error(self.pattern.len(), "expected `]`"));
+            };
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let lo = match c {
+                '\\' => match self.escape()?.as_slice() {
+                    [(lo, hi)] if lo == hi => *lo,
+                    class => {
+                        ranges.extend_from_slice(class);
+                        continue;
+                    }
+                },
+                c => c,
+            };
+            if self.chars.peek().map(|(_, c)| *c) == Some('-')
+                && !matches!(
+                    self.pattern[self.offset()..].chars().nth(1),
+                    Some(']') | None
+                )
+            {
+                self.chars.next();
+                let hi = match self.chars.next() {
+                    Some((_, '\\')) => match self.escape()?.as_slice() {
+                        [(lo, hi)] if lo == hi => *lo,
+                        _ => return Err(self.error(offset, "a class can't end a range")),
+                    },
+                    Some((_, c)) => c,
+                    None => return Err(self.error(self.pattern.len(), "expected `]`")),
+                };
+                if hi < lo {
+                    return Err(self.error(offset, "range out of order"));
+                }
+                ranges.push((lo, hi));
+            } else {
+                ranges.push((lo, lo));
+            }
+        }
+        let ranges = normalize(ranges);
+        Ok(Regex::Set(if negated {
+            complement(&ranges)
+        } else {
+            ranges
+        }))
+    }
+    /// The characters an escape sequence matches, after the `\`.
+    fn escape(&mut self) -> Result<Vec<(char, char)>, RegexError> {
+        let Some((offset, c)) = self.chars.next() else {
+            return Err(self.error(self.pattern.len(), "trailing `\\`"));
+        };
+        let digits = [('0', '9')];
+        let word = [('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+        let space = [('\t', '\r'), (' ', ' ')];
+        let single = |c| Ok(vec![(c, c)]);
+        match c {
+            'n' => single('\n'),
+            'r' => single('\r'),
+            't' => single('\t'),
+            'f' => single('\u{c}'),
+            'v' => single('\u{b}'),
+            '0' => single('\0'),
+            'd' => Ok(digits.to_vec()),
+            'D' => Ok(complement(&digits)),
+            'w' => Ok(word.to_vec()),
+            'W' => Ok(complement(&word)),
+            's' => Ok(space.to_vec()),
+            'S' => Ok(complement(&space)),
+            'x' => {
+                let hex: String = (0..2)
+                    .filter_map(|_| self.chars.next())
+                    .map(|(_, c)| c)
+                    .collect();
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 2)
+                    .and_then(char::from_u32)
+                    .map_or_else(
+                        || Err(self.error(offset, "expected two hex digits")),
+                        single,
+                    )
+            }
+            'u' => {
+                if !self.eat('{') {
+                    return Err(self.error(offset, "expected `{`"));
+                }
+                let mut hex = String::new();
+                while let Some((_, c)) = self.chars.next_if(|(_, c)| *c != '}') {
+                    hex.push(c);
+                }
+                if !self.eat('}') {
+                    return Err(self.error(offset, "expected `}`"));
+                }
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map_or_else(|| Err(self.error(offset, "invalid code point")), single)
+            }
+            c if c.is_ascii_alphanumeric() => Err(self.error(offset, "unknown escape")),
+            c => single(c),
+        }
+    }
+}
+
+/// Sorts `ranges` and merges the ones that overlap or touch.
+pub fn normalize(mut ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+    ranges.sort();
+    let mut res: Vec<(char, char)> = vec![];
+    for (lo, hi) in ranges {
+        match res.last_mut() {
+            Some((_, last)) if next_char(*last).is_none_or(|next| lo <= next) => {
+                *last = std::cmp::max(*last, hi);
+            }
+            _ => res.push((lo, hi)),
+        }
+    }
+    res
+}
+
+/// Every character not in `ranges`, which must be normalized.
+pub fn complement(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut res = vec![];
+    let mut next = Some('\0');
+    for (lo, hi) in ranges {
+        if let Some(from) = next.filter(|from| from < lo) {
+            res.push((from, prev_char(*lo).unwrap()));
+        }
+        next = next_char(*hi);
+    }
+    if let Some(from) = next {
+        res.push((from, char::MAX));
+    }
+    res
+}
+
+/// The character after `c`, skipping the surrogate code points.
+pub fn next_char(c: char) -> Option<char> {
+    match c {
+        '\u{d7ff}' => Some('\u{e000}'),
+        c => char::from_u32(c as u32 + 1),
+    }
+}
+
+fn prev_char(c: char) -> Option<char> {
+    match c {
+        '\u{e000}' => Some('\u{d7ff}'),
+        c => (c as u32).checked_sub(1).and_then(char::from_u32),
+    }
+}
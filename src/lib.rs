@@ -0,0 +1,10 @@
+pub mod build;
+pub mod c99grammar;
+pub mod c99lexer;
+pub mod c99typedefs;
+pub mod codegen;
+pub mod debug;
+pub mod grammar_file;
+pub mod lexing;
+pub mod parser_types;
+pub mod parser_utils;
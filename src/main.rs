@@ -1,23 +1,21 @@
-use c99grammar::Terminal;
-use parser_types::action_goto::{parse, ParsingTable};
-use parser_utils::first::compute_firsts;
+use std::{sync::Arc, thread};
 
-use crate::{
-    c99grammar::{c99rules, NonTerminal},
+use lr_1_parser::{
+    c99grammar::{c99rules, NonTerminal, Terminal},
+    c99lexer::c99lexer,
+    debug_println,
     parser_types::{
-        action_goto::{generate_parsing_table, print_parsing_table},
+        action_goto::{generate_parsing_table, print_parsing_table, ParsingTable},
         augmented_grammar::AugmentedGrammar,
+        compiled_parser::CompiledParser,
         lr1state::{format_lr1_state_machine, generate_lr1_statemachine},
         rule::Rule,
         terminal_or_nonterminal::TerminalOrNonTerminal,
+        trace::TraceEvent,
     },
+    parser_utils::first::compute_firsts,
 };
 
-pub mod c99grammar;
-pub mod debug;
-mod parser_types;
-mod parser_utils;
-
 fn main() {
     let augmented_grammar = AugmentedGrammar {
         start_rule: Rule {
@@ -27,6 +25,7 @@ fn main() {
             )],
         },
         rules: c99rules(),
+        entry_points: vec![NonTerminal::Expression, NonTerminal::Declaration],
     };
     let final_rules = augmented_grammar.rules();
     debug_println!("Final Rules: {:#?}", final_rules);
@@ -40,27 +39,42 @@ fn main() {
     );
     let parsing_table = generate_parsing_table(&final_rules, Some(&lr1_state_machine));
     print_parsing_table(&parsing_table, 1);
-    parse(
-        &[
-            Terminal::Int,
-            Terminal::Identifier,
-            Terminal::LeftParenthesis,
-            Terminal::Int,
-            Terminal::Identifier,
-            Terminal::Comma,
-            Terminal::Char,
-            Terminal::Multiply,
-            Terminal::Identifier,
-            Terminal::RightParenthesis,
-            Terminal::Semicolon,
-            Terminal::EOF,
-        ],
-        &final_rules,
-        &parsing_table,
-    );
-    let serialized = serde_json::to_string_pretty(&parsing_table).unwrap();
+    let compiled = Arc::new(CompiledParser::new(final_rules, parsing_table));
+    let lexer = c99lexer();
+    let declaration = lexer.tokenize("int f(int a, char *b);").unwrap();
+    let mut trace: Vec<TraceEvent<Terminal, NonTerminal>> = vec![];
+    let output = compiled
+        .session()
+        .with_observer(&mut trace)
+        .parse(declaration);
+    for event in &trace {
+        println!("{}", event);
+    }
+    for err in &output.errors {
+        println!("{}", err);
+    }
+    for repair in &output.repairs {
+        println!("repair: {}", repair);
+    }
+    if let Some(tree) = output.tree {
+        println!("{:#?}", tree);
+    }
+    let expression = lexer.tokenize("a * (b + 1)").unwrap();
+    let output = {
+        let compiled = Arc::clone(&compiled);
+        thread::spawn(move || compiled.parse(expression, NonTerminal::Expression))
+            .join()
+            .unwrap()
+    };
+    for err in &output.errors {
+        println!("{}", err);
+    }
+    if let Some(tree) = output.tree {
+        println!("{:#?}", tree);
+    }
+    let serialized = serde_json::to_string_pretty(compiled.parsing_table()).unwrap();
     println!("{}", serialized);
     let deserialized: ParsingTable<Terminal, NonTerminal> =
         serde_json::from_str(&serialized).unwrap();
-    println!("{}", parsing_table == deserialized);
+    println!("{}", *compiled.parsing_table() == deserialized);
 }
@@ -1,12 +1,22 @@
 use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
-use std::{cell::RefCell, collections::BTreeMap, iter::Peekable};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    iter::Peekable,
+};
 
 use super::{
+    lr1item::LR0Item,
     lr1state::{generate_lr1_statemachine, LR1StateMachine},
     nonterminal::NonTerminalTrait,
+    parse_error::ParseError,
+    parse_tree::ParseTree,
+    parser::Parser,
+    repair::Repair,
     rule::Rule,
     terminal::TerminalTrait,
     terminal_or_nonterminal::TerminalOrNonTerminal,
+    token::Token,
 };
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -29,8 +39,38 @@ impl std::fmt::Debug for Action {
 #[derive(Clone, PartialEq, Eq)]
 pub struct TwoKeyMap<K1, K2, V>(pub BTreeMap<(K1, K2), V>);
 pub type ActionTable<Terminal> = TwoKeyMap<usize, Terminal, Action>;
+pub type ConflictTable<Terminal> = TwoKeyMap<usize, Terminal, Vec<Action>>;
 pub type GoToTable<NonTerminal> = TwoKeyMap<usize, NonTerminal, usize>;
 
+impl<K1, K2, V> Default for TwoKeyMap<K1, K2, V> {
+    fn default() -> Self {
+        TwoKeyMap(BTreeMap::new())
+    }
+}
+
+impl<K2: Ord + Copy, V> TwoKeyMap<usize, K2, V> {
+    /// The entries whose first key is `k1`, ordered by the second. The map is ordered by the first
+    /// key, so they are found on both sides of `(k1, pivot)`, whether or not that key is in the
+    /// map, without going through the other rows.
+    fn row(&self, k1: usize, pivot: K2) -> impl Iterator<Item = (K2, &V)> {
+        let before: Vec<_> = self
+            .0
+            .range(..(k1, pivot))
+            .rev()
+            .take_while(|((k, _), _)| *k == k1)
+            .collect();
+        let after = self
+            .0
+            .range((k1, pivot)..)
+            .take_while(move |((k, _), _)| *k == k1);
+        before
+            .into_iter()
+            .rev()
+            .chain(after)
+            .map(|((_, k2), v)| (*k2, v))
+    }
+}
+
 impl<K1, K2, V> Serialize for TwoKeyMap<K1, K2, V>
 where
     K1: Serialize + Ord,
@@ -43,10 +83,7 @@ where
     {
         let mut ser = serializer.serialize_map(None)?;
         let mut iter = self.0.iter().map(|((k1, k2), v)| (k1, k2, v)).peekable();
-        loop {
-            let Some(&(key1, ..)) = iter.peek() else {
-                break;
-            };
+        while let Some(&(key1, ..)) = iter.peek() {
             ser.serialize_key(key1)?;
             let map_iter = MapIter {
                 iter: RefCell::new(&mut iter),
@@ -127,6 +164,151 @@ pub struct ParsingTable<
 > {
     action: ActionTable<Terminal>,
     goto: GoToTable<NonTerminal>,
+    /// The kernel items of every state, without lookaheads.
+    #[serde(default = "Default::default")]
+    kernels: BTreeMap<usize, Vec<LR0Item>>,
+    /// The initial state of every nonterminal a start rule derives.
+    #[serde(default = "Default::default")]
+    entry_states: BTreeMap<NonTerminal, usize>,
+    /// Every action of the cells with more than one, in the order they were generated. `action`
+    /// holds the last of them, which is the one a deterministic parser takes.
+    #[serde(default = "Default::default")]
+    conflicts: ConflictTable<Terminal>,
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + Serialize
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + Serialize
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > ParsingTable<Terminal, NonTerminal>
+{
+    pub fn action(&self, state: usize, terminal: Terminal) -> Option<Action> {
+        self.action.0.get(&(state, terminal)).copied()
+    }
+    /// Every action `terminal` has in `state`, for parsers that follow all of them.
+    pub fn actions(&self, state: usize, terminal: Terminal) -> Vec<Action> {
+        match self.conflicts.0.get(&(state, terminal)) {
+            Some(actions) => actions.clone(),
+            None => self.action(state, terminal).into_iter().collect(),
+        }
+    }
+    /// Every cell of the action table as `(state, terminal, action)`, by state and then terminal.
+    pub fn action_cells(&self) -> impl Iterator<Item = (usize, Terminal, Action)> + '_ {
+        self.action
+            .0
+            .iter()
+            .map(|((state, terminal), action)| (*state, *terminal, *action))
+    }
+    /// The cells with more than one action, as `(state, terminal, actions)`.
+    pub fn conflicts(&self) -> impl Iterator<Item = (usize, Terminal, &[Action])> {
+        self.conflicts
+            .0
+            .iter()
+            .map(|((state, terminal), actions)| (*state, *terminal, actions.as_slice()))
+    }
+    fn insert_action(&mut self, state: usize, terminal: Terminal, action: Action) {
+        match self.action.0.insert((state, terminal), action) {
+            Some(old) if old != action => {
+                let actions = self
+                    .conflicts
+                    .0
+                    .entry((state, terminal))
+                    .or_insert_with(|| vec![old]);
+                if !actions.contains(&action) {
+                    actions.push(action);
+                }
+            }
+            _ => {}
+        }
+    }
+    /// Takes `action` out of the cell of `state` and `terminal`, keeping its other actions.
+    pub fn remove_action(&mut self, state: usize, terminal: Terminal, action: Action) {
+        let key = (state, terminal);
+        let mut actions = self.actions(state, terminal);
+        actions.retain(|other| *other != action);
+        self.conflicts.0.remove(&key);
+        match actions.last() {
+            Some(last) => self.action.0.insert(key, *last),
+            None => self.action.0.remove(&key),
+        };
+        if actions.len() > 1 {
+            self.conflicts.0.insert(key, actions);
+        }
+    }
+    pub fn goto(&self, state: usize, nonterminal: NonTerminal) -> Option<usize> {
+        self.goto.0.get(&(state, nonterminal)).copied()
+    }
+    /// The state parsing `entry_point` starts from, if the table was generated with a start rule
+    /// for it. Tables serialized without entry states start from state 0, for the nonterminal of
+    /// their start rule only.
+    pub fn entry_state(&self, entry_point: NonTerminal) -> Option<usize> {
+        if self.entry_states.is_empty() {
+            return (self.start_nonterminal() == Some(entry_point)).then_some(0);
+        }
+        self.entry_states.get(&entry_point).copied()
+    }
+    /// The nonterminals parsing can start from.
+    pub fn entry_points(&self) -> Vec<NonTerminal> {
+        if self.entry_states.is_empty() {
+            return self.start_nonterminal().into_iter().collect();
+        }
+        self.entry_states.keys().copied().collect()
+    }
+    /// The nonterminal of the start rule, for tables serialized before entry states were
+    /// recorded: the one whose goto from state 0 accepts at the end of the input.
+    fn start_nonterminal(&self) -> Option<NonTerminal> {
+        self.goto
+            .row(0, NonTerminal::start())
+            .find(|(_, state)| self.action(**state, Terminal::eof()) == Some(Action::Accept))
+            .map(|(nonterminal, _)| nonterminal)
+    }
+    /// The kernel items of `state`, without lookaheads.
+    pub fn kernel(&self, state: usize) -> &[LR0Item] {
+        self.kernels
+            .get(&state)
+            .map_or(&[], |kernel| kernel.as_slice())
+    }
+    /// Every terminal with an action in `state`.
+    pub fn expected_terminals(&self, state: usize) -> Vec<Terminal> {
+        self.action
+            .row(state, Terminal::eof())
+            .map(|(t, _)| t)
+            .collect()
+    }
+    /// Every terminal with an action in some state.
+    pub fn terminals(&self) -> Vec<Terminal> {
+        let terminals: BTreeSet<Terminal> = self.action.0.keys().map(|(_, t)| *t).collect();
+        terminals.into_iter().collect()
+    }
+    /// The rule `state` reduces by whatever the lookahead, if that is the only action it has.
+    pub fn default_reduction(&self, state: usize) -> Option<usize> {
+        let mut res = None;
+        for (_, action) in self.action.row(state, Terminal::eof()) {
+            match (action, res) {
+                (Action::Reduce(n), None) => res = Some(*n),
+                (Action::Reduce(n), Some(m)) if *n == m => {}
+                _ => return None,
+            }
+        }
+        res
+    }
 }
 
 pub fn generate_parsing_table<
@@ -157,18 +339,38 @@ pub fn generate_parsing_table<
     let mut res = ParsingTable {
         action: TwoKeyMap(BTreeMap::new()),
         goto: TwoKeyMap(BTreeMap::new()),
+        kernels: BTreeMap::new(),
+        entry_states: BTreeMap::new(),
+        conflicts: TwoKeyMap(BTreeMap::new()),
     };
     let state_machine = match precomputed_state_machine {
         Some(sm) => sm.clone(),
         None => generate_lr1_statemachine(rules),
     };
     for (state_index, state) in state_machine {
+        let kernel: BTreeSet<LR0Item> = state
+            .kernel
+            .iter()
+            .map(|item| LR0Item {
+                index: item.index,
+                dot_index: item.dot_index,
+            })
+            .collect();
+        for item in &kernel {
+            if let (0, [TerminalOrNonTerminal::NonTerminal(entry_point)]) =
+                (item.dot_index, rules[item.index].rhs.as_slice())
+            {
+                if rules[item.index].lhs.is_start() {
+                    res.entry_states.insert(*entry_point, state_index);
+                }
+            }
+        }
+        res.kernels
+            .insert(state_index, kernel.into_iter().collect());
         for (t_or_nt, next_index) in state.transitions {
             match t_or_nt {
                 TerminalOrNonTerminal::Terminal(t) => {
-                    res.action
-                        .0
-                        .insert((state_index, t), Action::Shift(next_index));
+                    res.insert_action(state_index, t, Action::Shift(next_index));
                 }
                 TerminalOrNonTerminal::NonTerminal(nt) => {
                     res.goto.0.insert((state_index, nt), next_index);
@@ -177,14 +379,10 @@ pub fn generate_parsing_table<
         }
         for item in state.items {
             if item.next_symbol(rules).is_none() {
-                if Terminal::is_eof(&item.lookahead) && item.index == 0 {
-                    res.action
-                        .0
-                        .insert((state_index, Terminal::eof()), Action::Accept);
+                if Terminal::is_eof(&item.lookahead) && rules[item.index].lhs.is_start() {
+                    res.insert_action(state_index, Terminal::eof(), Action::Accept);
                 } else {
-                    res.action
-                        .0
-                        .insert((state_index, item.lookahead), Action::Reduce(item.index));
+                    res.insert_action(state_index, item.lookahead, Action::Reduce(item.index));
                 }
             }
         }
@@ -388,6 +586,72 @@ pub fn print_parsing_table<
     println!("╝");
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseOutput<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    /// The tree, if the input was accepted, possibly after recovering from errors.
+    pub tree: Option<ParseTree<Terminal, NonTerminal>>,
+    /// Every error encountered, in input order.
+    pub errors: Vec<ParseError<Terminal, NonTerminal>>,
+    /// The repairs applied to get past errors, in input order.
+    pub repairs: Vec<Repair<Terminal>>,
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > ParseOutput<Terminal, NonTerminal>
+{
+    /// The tree if the input parsed without any error.
+    pub fn into_result(
+        self,
+    ) -> Result<ParseTree<Terminal, NonTerminal>, Vec<ParseError<Terminal, NonTerminal>>> {
+        match self.tree {
+            Some(tree) if self.errors.is_empty() => Ok(tree),
+            _ => Err(self.errors),
+        }
+    }
+}
+
+/// Parses the tokens of `lex_stream` as they are produced. Tokens are pulled one at a time, the
+/// pulled token being the lookahead for the reductions before its shift, so a lexer can feed the
+/// parser directly. An end-of-input token is supplied if the stream does not end with one. Syntax
+/// errors are repaired where a small edit of the input lets parsing continue, and otherwise
+/// recovered from through the grammar's `error` rules where possible.
 pub fn parse<
     Terminal: std::fmt::Debug
         + Serialize
@@ -410,54 +674,35 @@ pub fn parse<
         + PartialOrd
         + Ord,
 >(
-    lex_stream: &[Terminal],
+    lex_stream: impl IntoIterator<Item = Token<Terminal>>,
     rules: &[Rule<Terminal, NonTerminal>],
     parsing_table: &ParsingTable<Terminal, NonTerminal>,
-) {
-    let mut lex_stream = lex_stream.to_owned();
-    let rules = rules.to_owned();
-    let mut state_stack = vec![0];
-    let mut parse_stack: Vec<TerminalOrNonTerminal<Terminal, NonTerminal>> = vec![];
-    loop {
-        println!("{:?}", state_stack);
-        println!("{:?}", parse_stack);
-        let state = state_stack.last().unwrap();
-        let token = lex_stream.first().unwrap();
-        let action = parsing_table.action.0.get(&(*state, *token));
-        match action {
-            Some(action) => match action {
-                Action::Shift(n) => {
-                    state_stack.push(*n);
-                    parse_stack.push(TerminalOrNonTerminal::Terminal(*token));
-                    lex_stream.remove(0);
-                }
-                Action::Reduce(n) => {
-                    let rule = rules[*n].clone();
-                    let rule_length = rule.rhs.len();
-                    let mut popped = vec![];
-                    for _ in 0..rule_length {
-                        state_stack.pop();
-                        popped.push(parse_stack.pop().unwrap());
-                    }
-                    let state = state_stack.last().unwrap();
-                    let goto = parsing_table.goto.0.get(&(*state, rule.lhs));
-                    match goto {
-                        Some(goto) => {
-                            state_stack.push(*goto);
-                            parse_stack.push(TerminalOrNonTerminal::NonTerminal(rule.lhs));
-                        }
-                        None => {
-                            panic!("No goto found");
-                        }
-                    }
-                }
-                Action::Accept => {
-                    break;
-                }
-            },
-            None => {
-                panic!("No action found");
-            }
-        }
+    entry_point: NonTerminal,
+) -> ParseOutput<Terminal, NonTerminal> {
+    match Parser::new(parsing_table, rules).with_entry_point(entry_point) {
+        Ok(mut parser) => parser.parse(lex_stream),
+        Err(err) => ParseOutput {
+            tree: None,
+            errors: vec![err],
+            repairs: vec![],
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_parsing_table;
+    use crate::grammar_file::{parse_grammar, NonTerminalName};
+
+    /// Tables serialized before entry states were recorded are read back without any.
+    #[test]
+    fn entry_state_without_entry_states() {
+        let grammar = parse_grammar("List -> List item | item ; Item -> item ;").unwrap();
+        let rules = grammar.rules();
+        let mut parsing_table = generate_parsing_table(&rules, None);
+        parsing_table.entry_states.clear();
+        assert_eq!(parsing_table.entry_state(NonTerminalName("List")), Some(0));
+        assert_eq!(parsing_table.entry_state(NonTerminalName("Item")), None);
+        assert_eq!(parsing_table.entry_points(), [NonTerminalName("List")]);
     }
 }
@@ -1,4 +1,7 @@
-use super::{nonterminal::NonTerminalTrait, rule::Rule, terminal::TerminalTrait};
+use super::{
+    nonterminal::NonTerminalTrait, rule::Rule, terminal::TerminalTrait,
+    terminal_or_nonterminal::TerminalOrNonTerminal,
+};
 
 pub struct AugmentedGrammar<
     Terminal: std::fmt::Debug
@@ -22,6 +25,9 @@ pub struct AugmentedGrammar<
 > {
     pub start_rule: Rule<Terminal, NonTerminal>,
     pub rules: Vec<Rule<Terminal, NonTerminal>>,
+    /// Nonterminals that can be parsed on their own besides the one `start_rule` derives. Each
+    /// gets a start rule of its own, and with it an initial state in the parsing table.
+    pub entry_points: Vec<NonTerminal>,
 }
 
 impl<
@@ -45,9 +51,15 @@ impl<
             + Ord,
     > AugmentedGrammar<Terminal, NonTerminal>
 {
+    /// The start rule, then the grammar's rules, then a start rule for every entry point. The
+    /// entry points come last so the other rules keep their numbers when they are added.
     pub fn rules(&self) -> Vec<Rule<Terminal, NonTerminal>> {
         let mut rules = vec![self.start_rule.clone()];
         rules.extend(self.rules.iter().cloned());
+        rules.extend(self.entry_points.iter().map(|entry_point| Rule {
+            lhs: NonTerminal::start(),
+            rhs: vec![TerminalOrNonTerminal::NonTerminal(*entry_point)],
+        }));
         rules
     }
 }
@@ -0,0 +1,144 @@
+use serde::Serialize;
+
+use super::{
+    action_goto::{generate_parsing_table, parse, ParseOutput, ParsingTable},
+    augmented_grammar::AugmentedGrammar,
+    glr::GlrParser,
+    nonterminal::NonTerminalTrait,
+    parser::Parser,
+    rule::Rule,
+    terminal::TerminalTrait,
+    token::Token,
+};
+
+/// The rules of a grammar together with the parsing table generated from them, built once and
+/// shared by every parse. It is immutable and `Send + Sync` whenever the terminal and nonterminal
+/// types are, so it can be put in an `Arc` and used from many threads at once, each parse running
+/// in a [`Parser`] session of its own that only borrows it.
+#[derive(Clone, PartialEq)]
+pub struct CompiledParser<
+    Terminal: std::fmt::Debug
+        + Serialize
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    rules: Vec<Rule<Terminal, NonTerminal>>,
+    parsing_table: ParsingTable<Terminal, NonTerminal>,
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + Serialize
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + Serialize
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > CompiledParser<Terminal, NonTerminal>
+{
+    /// Bundles `rules` with a table generated from them, for instance one deserialized from a
+    /// previous run.
+    pub fn new(
+        rules: Vec<Rule<Terminal, NonTerminal>>,
+        parsing_table: ParsingTable<Terminal, NonTerminal>,
+    ) -> Self {
+        Self {
+            rules,
+            parsing_table,
+        }
+    }
+    /// Generates the parsing table of `grammar`.
+    pub fn from_grammar(grammar: &AugmentedGrammar<Terminal, NonTerminal>) -> Self {
+        let rules = grammar.rules();
+        let parsing_table = generate_parsing_table(&rules, None);
+        Self::new(rules, parsing_table)
+    }
+    pub fn rules(&self) -> &[Rule<Terminal, NonTerminal>] {
+        &self.rules
+    }
+    pub fn parsing_table(&self) -> &ParsingTable<Terminal, NonTerminal> {
+        &self.parsing_table
+    }
+    /// A parser for one input. Only the stacks of the parse are allocated; the rules and table
+    /// are borrowed.
+    pub fn session(&self) -> Parser<'_, Terminal, NonTerminal> {
+        Parser::new(&self.parsing_table, &self.rules)
+    }
+    /// A parser following every action of the conflicting cells of the table, for inputs the
+    /// grammar is ambiguous on.
+    pub fn glr(&self) -> GlrParser<'_, Terminal, NonTerminal> {
+        GlrParser::new(&self.parsing_table, &self.rules)
+    }
+    /// Parses `lex_stream` from `entry_point` in a new session.
+    pub fn parse(
+        &self,
+        lex_stream: impl IntoIterator<Item = Token<Terminal>>,
+        entry_point: NonTerminal,
+    ) -> ParseOutput<Terminal, NonTerminal> {
+        parse(lex_stream, &self.rules, &self.parsing_table, entry_point)
+    }
+}
+
+/// Fails to compile if a field that isn't thread safe is added.
+const _: () = {
+    fn assert<T: Send + Sync>() {}
+    // Never called, but its body is still type checked for every terminal and nonterminal type.
+    #[allow(dead_code)]
+    fn assert_compiled_parser<
+        Terminal: std::fmt::Debug
+            + Serialize
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd
+            + Send
+            + Sync,
+        NonTerminal: std::fmt::Debug
+            + Serialize
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord
+            + Send
+            + Sync,
+    >() {
+        assert::<CompiledParser<Terminal, NonTerminal>>();
+    }
+};
@@ -0,0 +1,127 @@
+use serde::Serialize;
+
+use super::{
+    action_goto::ParsingTable,
+    nonterminal::NonTerminalTrait,
+    parse_error::ParseError,
+    parser::{advance, simulate},
+    rule::Rule,
+    terminal::TerminalTrait,
+    token::Token,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion<Terminal, NonTerminal> {
+    /// Every terminal that may follow the prefix, including the end-of-input terminal if the
+    /// prefix is a complete input.
+    pub terminals: Vec<Terminal>,
+    /// The nonterminals partially recognized at the end of the prefix, innermost first: the
+    /// left-hand sides of the kernel items of the states on the stack, once the reductions made
+    /// whatever the lookahead are done, that have their dot after the start and before the end
+    /// of the rule.
+    pub in_progress: Vec<NonTerminal>,
+}
+
+/// What may follow `prefix`, parsed from `entry_point`, for autocompletion. A terminal is only
+/// offered if it is eventually shifted after the reductions it triggers, so states that would
+/// reduce on it and then reject it do not contribute.
+pub fn complete<
+    Terminal: std::fmt::Debug
+        + Serialize
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>(
+    prefix: impl IntoIterator<Item = Token<Terminal>>,
+    rules: &[Rule<Terminal, NonTerminal>],
+    parsing_table: &ParsingTable<Terminal, NonTerminal>,
+    entry_point: NonTerminal,
+) -> Result<Completion<Terminal, NonTerminal>, ParseError<Terminal, NonTerminal>> {
+    let Some(start) = parsing_table.entry_state(entry_point) else {
+        return Err(ParseError::UnknownEntryPoint {
+            nonterminal: entry_point,
+        });
+    };
+    let mut state_stack = vec![start];
+    for token in prefix {
+        if let Err(state) = advance(parsing_table, rules, &mut state_stack, token.kind) {
+            return Err(ParseError::UnexpectedToken {
+                state,
+                expected: parsing_table.expected_terminals(state),
+                token,
+            });
+        }
+    }
+    let terminals = parsing_table
+        .terminals()
+        .into_iter()
+        .filter(|t| !t.is_error())
+        .filter(|t| simulate(parsing_table, rules, state_stack.iter().rev().copied(), *t).is_ok())
+        .collect();
+    // The reductions made whatever comes next are made first, so the constructs they complete
+    // are in progress.
+    while let Some(n) = parsing_table.default_reduction(*state_stack.last().unwrap()) {
+        let rule = &rules[n];
+        state_stack.truncate(state_stack.len() - rule.rhs.len());
+        match parsing_table.goto(*state_stack.last().unwrap(), rule.lhs) {
+            Some(goto) => state_stack.push(goto),
+            None => break,
+        }
+    }
+    let mut in_progress = vec![];
+    for state in state_stack.iter().rev() {
+        for item in parsing_table.kernel(*state) {
+            let rule = &rules[item.index];
+            let started = item.dot_index > 0 && item.dot_index < rule.rhs.len();
+            if started && !rule.lhs.is_start() && !in_progress.contains(&rule.lhs) {
+                in_progress.push(rule.lhs);
+            }
+        }
+    }
+    Ok(Completion {
+        terminals,
+        in_progress,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::complete;
+    use crate::{
+        grammar_file::{parse_grammar, NonTerminalName, TerminalName},
+        parser_types::{
+            action_goto::generate_parsing_table,
+            token::{Position, Span, Token},
+        },
+    };
+
+    #[test]
+    fn in_progress_after_default_reductions() {
+        let grammar = parse_grammar("S -> X y ; X -> a ;").unwrap();
+        let rules = grammar.rules();
+        let parsing_table = generate_parsing_table(&rules, None);
+        let prefix = [Token::new(
+            TerminalName("a"),
+            "a",
+            Span::empty(Position::default()),
+        )];
+        let completion = complete(prefix, &rules, &parsing_table, NonTerminalName("S")).unwrap();
+        assert_eq!(completion.terminals, vec![TerminalName("y")]);
+        assert_eq!(completion.in_progress, vec![NonTerminalName("S")]);
+    }
+}
@@ -0,0 +1,494 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{
+    forest::{Forest, ForestNode, Packed},
+    nonterminal::NonTerminalTrait,
+    parse_error::ParseError,
+    parse_tree::ParseTree,
+    terminal::TerminalTrait,
+    token::Token,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`.
+    Left,
+    /// `a = b = c` is `a = (b = c)`.
+    Right,
+    /// `a < b < c` is rejected.
+    NonAssoc,
+}
+
+/// How to settle an ambiguity that a shift/reduce conflict of the parsing table led to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Preference {
+    /// Keep the derivation where the terminal was shifted, e.g. to attach a dangling `else` to
+    /// the innermost `if`.
+    Shift,
+    /// Keep the derivation where the rule was reduced before the terminal was read.
+    Reduce,
+}
+
+/// A custom filter, given the forest, a node and one of its alternatives. The alternative is kept
+/// only if it returns `true`.
+pub type Filter<Terminal, NonTerminal> =
+    Box<dyn Fn(&Forest<Terminal, NonTerminal>, usize, &Packed) -> bool>;
+
+/// The parent of a node: its rule and whether the node is its first and its last child. `None` at
+/// the root.
+type Context = Option<(usize, bool, bool)>;
+
+/// Picks one tree out of a [`Forest`] with declarative filters, in the manner of SDF. Rules are
+/// referred to by their index in the rules the parsing table was built from.
+///
+/// - Priorities: with `higher` over `lower`, a node derived by `lower` can't be the first or last
+///   child of one derived by `higher`. Children enclosed by terminals on both sides, like the
+///   inside of parentheses, are left alone. Priorities are transitive.
+/// - Associativity: within a group of rules, a node derived by one of them can't be the last
+///   child of another when the group is left associative, or the first when it is right
+///   associative, and neither when it is non-associative.
+/// - Rejects: a node with a derivation by a rejected rule is dropped with all its derivations,
+///   so a reject rule can carve keywords out of identifiers.
+/// - Preferences settle what is left from shift/reduce conflicts, see [`Preference`].
+/// - Custom filters drop any derivation they return `false` on.
+///
+/// Priorities, associativity, rejects and custom filters drop derivations wherever they are;
+/// preferences only choose between the derivations that remain at an ambiguous node, so they
+/// never make the input fail to parse. Derivations going through a cycle are dropped.
+pub struct Disambiguator<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    /// Pairs of rules `(higher, lower)`, closed under transitivity.
+    priorities: BTreeSet<(usize, usize)>,
+    /// The associativity of every rule in a group, and the group.
+    associativity: BTreeMap<usize, (Associativity, usize)>,
+    groups: usize,
+    rejects: BTreeSet<usize>,
+    preferences: Vec<(usize, Terminal, Preference)>,
+    filters: Vec<Filter<Terminal, NonTerminal>>,
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > Default for Disambiguator<Terminal, NonTerminal>
+{
+    fn default() -> Self {
+        Self {
+            priorities: BTreeSet::new(),
+            associativity: BTreeMap::new(),
+            groups: 0,
+            rejects: BTreeSet::new(),
+            preferences: vec![],
+            filters: vec![],
+        }
+    }
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > Disambiguator<Terminal, NonTerminal>
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Gives rule `higher` priority over rule `lower`.
+    pub fn with_priority(mut self, higher: usize, lower: usize) -> Self {
+        let above: Vec<usize> = std::iter::once(higher)
+            .chain(
+                self.priorities
+                    .iter()
+                    .filter(|(_, l)| *l == higher)
+                    .map(|(h, _)| *h),
+            )
+            .collect();
+        let below: Vec<usize> = std::iter::once(lower)
+            .chain(
+                self.priorities
+                    .iter()
+                    .filter(|(h, _)| *h == lower)
+                    .map(|(_, l)| *l),
+            )
+            .collect();
+        for h in &above {
+            for l in &below {
+                self.priorities.insert((*h, *l));
+            }
+        }
+        self
+    }
+    /// Makes `rules` a group of the same precedence with the given associativity. A rule belongs
+    /// to at most one group; adding it to another moves it.
+    pub fn with_associativity(mut self, associativity: Associativity, rules: &[usize]) -> Self {
+        for rule in rules {
+            self.associativity
+                .insert(*rule, (associativity, self.groups));
+        }
+        self.groups += 1;
+        self
+    }
+    pub fn with_reject(mut self, rule: usize) -> Self {
+        self.rejects.insert(rule);
+        self
+    }
+    /// Settles ambiguities between reducing `rule` before `terminal` and shifting `terminal`.
+    /// Preferences are applied in the order they are added.
+    pub fn with_preference(
+        mut self,
+        rule: usize,
+        terminal: Terminal,
+        preference: Preference,
+    ) -> Self {
+        self.preferences.push((rule, terminal, preference));
+        self
+    }
+    pub fn with_filter(
+        mut self,
+        filter: impl Fn(&Forest<Terminal, NonTerminal>, usize, &Packed) -> bool + 'static,
+    ) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+    /// The one tree of `forest` the filters leave. Fails with [`ParseError::Ambiguous`] at the
+    /// outermost node left with several derivations, or with [`ParseError::NoDerivation`] if
+    /// none are left.
+    pub fn resolve(
+        &self,
+        forest: &Forest<Terminal, NonTerminal>,
+    ) -> Result<ParseTree<Terminal, NonTerminal>, ParseError<Terminal, NonTerminal>> {
+        let viable = self.viable(forest);
+        self.build(forest, &viable, (forest.root(), None), None, true)
+    }
+    /// Whether the alternative `packed` of node `id` passes the filters that only look at it and
+    /// its parent.
+    fn allows(
+        &self,
+        forest: &Forest<Terminal, NonTerminal>,
+        id: usize,
+        packed: &Packed,
+        context: Context,
+    ) -> bool {
+        if let Some((parent, first, last)) = context {
+            if (first || last) && self.priorities.contains(&(parent, packed.rule)) {
+                return false;
+            }
+            match (
+                self.associativity.get(&parent),
+                self.associativity.get(&packed.rule),
+            ) {
+                (Some((associativity, group)), Some((_, other))) if group == other => {
+                    let forbidden = match associativity {
+                        Associativity::Left => last,
+                        Associativity::Right => first,
+                        Associativity::NonAssoc => first || last,
+                    };
+                    if forbidden {
+                        return false;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.filters.iter().all(|filter| filter(forest, id, packed))
+    }
+    /// The alternatives of every node reachable from the root, in every context it is reached in,
+    /// that have a derivation passing the filters. Computed bottom up with an explicit stack, so
+    /// deep forests don't overflow the call stack.
+    fn viable(
+        &self,
+        forest: &Forest<Terminal, NonTerminal>,
+    ) -> BTreeMap<(usize, Context), Vec<usize>> {
+        let mut viable: BTreeMap<(usize, Context), Vec<usize>> = BTreeMap::new();
+        let mut visiting = BTreeSet::new();
+        let mut stack = vec![(forest.root(), None, false)];
+        while let Some((id, context, expanded)) = stack.pop() {
+            let key = (id, context);
+            let ForestNode::Node { alternatives, .. } = forest.node(id) else {
+                continue;
+            };
+            if viable.contains_key(&key) || (!expanded && !visiting.insert(key)) {
+                continue;
+            }
+            let rejected = alternatives
+                .iter()
+                .any(|packed| self.rejects.contains(&packed.rule));
+            let allowed = alternatives
+                .iter()
+                .enumerate()
+                .filter(|(_, packed)| !rejected && self.allows(forest, id, packed, context))
+                .map(|(index, packed)| (index, child_keys(packed)));
+            if !expanded {
+                stack.push((id, context, true));
+                for (_, children) in allowed {
+                    for (child, context) in children {
+                        let child_key = (child, context);
+                        if !viable.contains_key(&child_key) && !visiting.contains(&child_key) {
+                            stack.push((child, context, false));
+                        }
+                    }
+                }
+            } else {
+                let res = allowed
+                    .filter_map(|(index, mut children)| {
+                        children
+                            .all(|key| {
+                                matches!(forest.node(key.0), ForestNode::Leaf(_))
+                                    || viable.get(&key).is_some_and(|v| !v.is_empty())
+                            })
+                            .then_some(index)
+                    })
+                    .collect();
+                visiting.remove(&key);
+                viable.insert(key, res);
+            }
+        }
+        viable
+    }
+    /// Builds the tree of node `id` reached in `context`, taking alternative `choice` there if
+    /// given. When `strict`, fails at the first node left with several alternatives; otherwise
+    /// takes the first.
+    fn build(
+        &self,
+        forest: &Forest<Terminal, NonTerminal>,
+        viable: &BTreeMap<(usize, Context), Vec<usize>>,
+        (id, context): (usize, Context),
+        choice: Option<usize>,
+        strict: bool,
+    ) -> Result<ParseTree<Terminal, NonTerminal>, ParseError<Terminal, NonTerminal>> {
+        // Built bottom up with explicit stacks, so deep trees don't overflow the call stack. The
+        // trees of the children of a node are on top of `trees` when it is built.
+        let mut trees = vec![];
+        let mut stack = vec![(id, context, choice, false)];
+        while let Some((id, context, chosen, expanded)) = stack.pop() {
+            let key = (id, context);
+            let (lhs, span, alternatives) = match forest.node(id) {
+                ForestNode::Leaf(token) => {
+                    trees.push(ParseTree::Leaf(token.clone()));
+                    continue;
+                }
+                ForestNode::Node {
+                    lhs,
+                    span,
+                    alternatives,
+                } => (*lhs, *span, alternatives),
+            };
+            if expanded {
+                let packed = &alternatives[chosen.unwrap()];
+                let children = trees.split_off(trees.len() - packed.children.len());
+                trees.push(ParseTree::node(lhs, packed.rule, children, &span));
+                continue;
+            }
+            let choice = match chosen {
+                Some(choice) => choice,
+                None => {
+                    let candidates = self.prefer(
+                        forest,
+                        alternatives,
+                        viable.get(&key).cloned().unwrap_or_default(),
+                    );
+                    match candidates.as_slice() {
+                        [] => {
+                            return Err(ParseError::NoDerivation {
+                                nonterminal: lhs,
+                                span,
+                            })
+                        }
+                        [_, _, ..] if strict => {
+                            return Err(ParseError::Ambiguous {
+                                nonterminal: lhs,
+                                span,
+                                alternatives: candidates
+                                    .iter()
+                                    .map(|choice| {
+                                        self.build(forest, viable, key, Some(*choice), false)
+                                    })
+                                    .collect::<Result<_, _>>()?,
+                            });
+                        }
+                        [choice, ..] => *choice,
+                    }
+                }
+            };
+            stack.push((id, context, Some(choice), true));
+            stack.extend(
+                child_keys(&alternatives[choice])
+                    .rev()
+                    .map(|(child, context)| (child, context, None, false)),
+            );
+        }
+        Ok(trees.pop().unwrap())
+    }
+    /// Narrows `candidates`, alternatives of one node, down with the preferences.
+    fn prefer(
+        &self,
+        forest: &Forest<Terminal, NonTerminal>,
+        alternatives: &[Packed],
+        mut candidates: Vec<usize>,
+    ) -> Vec<usize> {
+        for (rule, terminal, preference) in &self.preferences {
+            if candidates.len() < 2 {
+                break;
+            }
+            let (reduced, shifted): (Vec<usize>, Vec<usize>) =
+                candidates.iter().partition(|index| {
+                    alternatives[**index].children.windows(2).any(|pair| {
+                        first_token(forest, pair[1]).is_some_and(|token| token.kind == *terminal)
+                            && ends_with(forest, pair[0], *rule)
+                    })
+                });
+            let keep = match preference {
+                Preference::Shift => shifted,
+                Preference::Reduce => reduced,
+            };
+            if !keep.is_empty() {
+                candidates = keep;
+            }
+        }
+        candidates
+    }
+}
+
+/// The children of `packed` with the context each is reached in.
+fn child_keys(packed: &Packed) -> impl DoubleEndedIterator<Item = (usize, Context)> + '_ {
+    let len = packed.children.len();
+    packed
+        .children
+        .iter()
+        .enumerate()
+        .map(move |(i, child)| (*child, Some((packed.rule, i == 0, i + 1 == len))))
+}
+
+/// The first token below node `id`.
+fn first_token<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>(
+    forest: &Forest<Terminal, NonTerminal>,
+    id: usize,
+) -> Option<&Token<Terminal>> {
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![id];
+    while let Some(id) = stack.pop() {
+        match forest.node(id) {
+            ForestNode::Leaf(token) => return Some(token),
+            // Every alternative covers the same tokens.
+            ForestNode::Node { alternatives, .. } => {
+                if visited.insert(id) {
+                    stack.extend(alternatives[0].children.iter().rev());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Whether some derivation of node `id` has a node derived by `rule` at its right edge, the node
+/// itself included.
+fn ends_with<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>(
+    forest: &Forest<Terminal, NonTerminal>,
+    id: usize,
+    rule: usize,
+) -> bool {
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![id];
+    while let Some(id) = stack.pop() {
+        let ForestNode::Node { alternatives, .. } = forest.node(id) else {
+            continue;
+        };
+        if !visited.insert(id) {
+            continue;
+        }
+        for packed in alternatives {
+            if packed.rule == rule {
+                return true;
+            }
+            stack.extend(packed.children.last());
+        }
+    }
+    false
+}
@@ -0,0 +1,348 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{
+    disambiguation::Disambiguator,
+    forest::{Forest, ForestNode, Packed},
+    nonterminal::NonTerminalTrait,
+    parse_error::ParseError,
+    parse_tree::ParseTree,
+    rule::Rule,
+    terminal::TerminalTrait,
+    terminal_or_nonterminal::TerminalOrNonTerminal,
+    token::{Position, Span, Token},
+};
+
+/// An Earley item: a rule, how much of its right-hand side has been read, and the number of the
+/// set it was predicted in.
+type Item = (usize, usize, usize);
+
+/// An Earley parser working directly from the rules, with no table to generate, so it accepts
+/// any context-free grammar: ambiguous ones, ones with conflicts, and ones with empty rules,
+/// which are handled as in Aycock and Horspool's "Practical Earley Parsing". It runs in cubic
+/// time at worst, and in linear time on most LR(1) grammars, but is slower than the LR parsers
+/// by a large constant, so it is meant for grammars being written and for cross-checking the
+/// LR drivers, which it builds the same trees as.
+///
+/// There is no error recovery. Errors are reported as [`ParseError::UnexpectedToken`] with the
+/// number of tokens read before the unexpected one as the state.
+pub struct EarleyParser<
+    'a,
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    rules: &'a [Rule<Terminal, NonTerminal>],
+    /// The numbers of the rules of every nonterminal.
+    rules_of: BTreeMap<NonTerminal, Vec<usize>>,
+    nullable: BTreeSet<NonTerminal>,
+    start_rule: usize,
+}
+
+impl<
+        'a,
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > EarleyParser<'a, Terminal, NonTerminal>
+{
+    /// A parser for `rules`, as returned by
+    /// [`crate::parser_types::augmented_grammar::AugmentedGrammar::rules`]. Parsing starts from
+    /// the first start rule.
+    pub fn new(rules: &'a [Rule<Terminal, NonTerminal>]) -> Self {
+        let mut rules_of: BTreeMap<NonTerminal, Vec<usize>> = BTreeMap::new();
+        for (index, rule) in rules.iter().enumerate() {
+            rules_of.entry(rule.lhs).or_default().push(index);
+        }
+        let mut nullable = BTreeSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in rules {
+                if !nullable.contains(&rule.lhs)
+                    && rule.rhs.iter().all(|symbol| match symbol {
+                        TerminalOrNonTerminal::Terminal(_) => false,
+                        TerminalOrNonTerminal::NonTerminal(nonterminal) => {
+                            nullable.contains(nonterminal)
+                        }
+                    })
+                {
+                    nullable.insert(rule.lhs);
+                    changed = true;
+                }
+            }
+        }
+        Self {
+            rules,
+            rules_of,
+            nullable,
+            start_rule: rules
+                .iter()
+                .position(|rule| rule.lhs.is_start())
+                .unwrap_or(0),
+        }
+    }
+    /// Parses `entry_point` instead of the nonterminal the first start rule derives. The rules
+    /// must have a start rule for it.
+    pub fn with_entry_point(
+        mut self,
+        entry_point: NonTerminal,
+    ) -> Result<Self, ParseError<Terminal, NonTerminal>> {
+        match self.rules.iter().position(|rule| {
+            rule.lhs.is_start() && rule.rhs == [TerminalOrNonTerminal::NonTerminal(entry_point)]
+        }) {
+            Some(start_rule) => {
+                self.start_rule = start_rule;
+                Ok(self)
+            }
+            None => Err(ParseError::UnknownEntryPoint {
+                nonterminal: entry_point,
+            }),
+        }
+    }
+    /// Whether `lex_stream` parses.
+    pub fn recognize(&self, lex_stream: impl IntoIterator<Item = Token<Terminal>>) -> bool {
+        self.chart(lex_stream).is_ok()
+    }
+    /// The tree of `lex_stream`, or [`ParseError::Ambiguous`] if it has several.
+    pub fn parse(
+        &self,
+        lex_stream: impl IntoIterator<Item = Token<Terminal>>,
+    ) -> Result<ParseTree<Terminal, NonTerminal>, ParseError<Terminal, NonTerminal>> {
+        Disambiguator::new().resolve(&self.parse_forest(lex_stream)?)
+    }
+    /// Every parse of `lex_stream`, in a forest shaped like the one
+    /// [`crate::parser_types::glr::GlrParser`] builds.
+    pub fn parse_forest(
+        &self,
+        lex_stream: impl IntoIterator<Item = Token<Terminal>>,
+    ) -> Result<Forest<Terminal, NonTerminal>, ParseError<Terminal, NonTerminal>> {
+        let (chart, tokens) = self.chart(lex_stream)?;
+        // The sets each nonterminal completed from a set ends in.
+        let mut ends: BTreeMap<(NonTerminal, usize), Vec<usize>> = BTreeMap::new();
+        for (end, set) in chart.iter().enumerate() {
+            for (rule, dot, origin) in set {
+                if *dot == self.rules[*rule].rhs.len() {
+                    let ends = ends.entry((self.rules[*rule].lhs, *origin)).or_default();
+                    if ends.last() != Some(&end) {
+                        ends.push(end);
+                    }
+                }
+            }
+        }
+
+        let leaves = tokens.len() - 1;
+        let mut nodes: Vec<_> = tokens[..leaves]
+            .iter()
+            .map(|token| ForestNode::Leaf(token.clone()))
+            .collect();
+        let mut ids = BTreeMap::new();
+        let TerminalOrNonTerminal::NonTerminal(entry_point) = self.rules[self.start_rule].rhs[0]
+        else {
+            unreachable!("start rules derive a nonterminal")
+        };
+        let mut queue = vec![];
+        let mut node_id = |nodes: &mut Vec<ForestNode<Terminal, NonTerminal>>,
+                           queue: &mut Vec<(NonTerminal, usize, usize)>,
+                           key: (NonTerminal, usize, usize)| {
+            *ids.entry(key).or_insert_with(|| {
+                let (lhs, start, end) = key;
+                let span = if start < end {
+                    Span::new(tokens[start].span.start, tokens[end - 1].span.end)
+                } else {
+                    Span::empty(tokens[end].span.start)
+                };
+                nodes.push(ForestNode::Node {
+                    lhs,
+                    span,
+                    alternatives: vec![],
+                });
+                queue.push(key);
+                nodes.len() - 1
+            })
+        };
+        let root = node_id(&mut nodes, &mut queue, (entry_point, 0, leaves));
+        while let Some((lhs, start, end)) = queue.pop() {
+            let id = node_id(&mut nodes, &mut queue, (lhs, start, end));
+            for &rule in &self.rules_of[&lhs] {
+                if !chart[end].contains(&(rule, self.rules[rule].rhs.len(), start)) {
+                    continue;
+                }
+                // Splits of the right-hand side between `start` and `end`, found depth first
+                // through the items the chart has for the rule.
+                let mut splits = vec![(vec![], start)];
+                while let Some((children, at)) = splits.pop() {
+                    let dot = children.len();
+                    let Some(symbol) = self.rules[rule].rhs.get(dot) else {
+                        if at == end {
+                            let mut ids = vec![];
+                            for child in children {
+                                ids.push(match child {
+                                    Ok(leaf) => leaf,
+                                    Err(key) => node_id(&mut nodes, &mut queue, key),
+                                });
+                            }
+                            let packed = Packed {
+                                rule,
+                                children: ids,
+                            };
+                            if let ForestNode::Node { alternatives, .. } = &mut nodes[id] {
+                                alternatives.push(packed);
+                            }
+                        }
+                        continue;
+                    };
+                    let next: Vec<_> = match symbol {
+                        TerminalOrNonTerminal::Terminal(terminal) => {
+                            if at < end && tokens[at].kind == *terminal {
+                                vec![(Ok(at), at + 1)]
+                            } else {
+                                vec![]
+                            }
+                        }
+                        TerminalOrNonTerminal::NonTerminal(nonterminal) => {
+                            let ends = ends
+                                .get(&(*nonterminal, at))
+                                .map_or(&[][..], |ends| ends.as_slice());
+                            // The last symbol has to end where the rule does.
+                            let ends = if dot + 1 == self.rules[rule].rhs.len() {
+                                ends.binary_search(&end).map_or(&[][..], |i| &ends[i..=i])
+                            } else {
+                                &ends[..ends.partition_point(|to| *to <= end)]
+                            };
+                            ends.iter()
+                                .map(|to| (Err((*nonterminal, at, *to)), *to))
+                                .collect()
+                        }
+                    };
+                    for (child, to) in next {
+                        if chart[to].contains(&(rule, dot + 1, start)) {
+                            let mut children = children.clone();
+                            children.push(child);
+                            splits.push((children, to));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Forest::new(nodes, root))
+    }
+    /// The Earley sets of `lex_stream`, with the tokens read and the end-of-input token.
+    #[allow(clippy::type_complexity)]
+    fn chart(
+        &self,
+        lex_stream: impl IntoIterator<Item = Token<Terminal>>,
+    ) -> Result<(Vec<BTreeSet<Item>>, Vec<Token<Terminal>>), ParseError<Terminal, NonTerminal>>
+    {
+        let mut lex_stream = lex_stream.into_iter();
+        let mut chart = vec![];
+        // The items of every set waiting for each nonterminal.
+        let mut waiting: Vec<BTreeMap<NonTerminal, Vec<Item>>> = vec![];
+        let mut tokens: Vec<Token<Terminal>> = vec![];
+        let mut set = BTreeSet::new();
+        let mut pending = vec![(self.start_rule, 0, 0)];
+        loop {
+            let index = chart.len();
+            let token = lex_stream
+                .next()
+                .filter(|token| !token.kind.is_eof())
+                .unwrap_or_else(|| {
+                    Token::eof(tokens.last().map_or(Position::default(), |t| t.span.end))
+                });
+            waiting.push(BTreeMap::new());
+            let mut next = vec![];
+            while let Some(item) = pending.pop() {
+                if !set.insert(item) {
+                    continue;
+                }
+                let (rule, dot, origin) = item;
+                match self.rules[rule].rhs.get(dot) {
+                    Some(TerminalOrNonTerminal::Terminal(terminal)) => {
+                        if *terminal == token.kind {
+                            next.push((rule, dot + 1, origin));
+                        }
+                    }
+                    Some(TerminalOrNonTerminal::NonTerminal(nonterminal)) => {
+                        waiting[index].entry(*nonterminal).or_default().push(item);
+                        pending.extend(
+                            self.rules_of
+                                .get(nonterminal)
+                                .into_iter()
+                                .flatten()
+                                .map(|rule| (*rule, 0, index)),
+                        );
+                        if self.nullable.contains(nonterminal) {
+                            pending.push((rule, dot + 1, origin));
+                        }
+                    }
+                    None => {
+                        // Items of this set waiting for a nullable nonterminal were advanced past
+                        // it when they were added, so empty completions don't have to be
+                        // replayed for items added after them.
+                        let lhs = self.rules[rule].lhs;
+                        pending.extend(
+                            waiting[origin]
+                                .get(&lhs)
+                                .into_iter()
+                                .flatten()
+                                .map(|(rule, dot, origin)| (*rule, dot + 1, *origin)),
+                        );
+                    }
+                }
+            }
+            let accepted = set.contains(&(self.start_rule, 1, 0));
+            if next.is_empty() || token.kind.is_eof() {
+                if token.kind.is_eof() && accepted {
+                    chart.push(set);
+                    tokens.push(token);
+                    return Ok((chart, tokens));
+                }
+                let expected: BTreeSet<Terminal> = set
+                    .iter()
+                    .filter_map(|(rule, dot, _)| match self.rules[*rule].rhs.get(*dot) {
+                        Some(TerminalOrNonTerminal::Terminal(terminal)) => Some(*terminal),
+                        _ => None,
+                    })
+                    .chain(accepted.then(Terminal::eof))
+                    .collect();
+                return Err(ParseError::UnexpectedToken {
+                    state: index,
+                    token,
+                    expected: expected.into_iter().collect(),
+                });
+            }
+            chart.push(std::mem::take(&mut set));
+            tokens.push(token);
+            pending = next;
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeSet;
+
+use super::{
+    nonterminal::NonTerminalTrait,
+    parse_tree::ParseTree,
+    terminal::TerminalTrait,
+    token::{Span, Token},
+};
+
+/// One way of deriving a [`ForestNode::Node`]: the rule reduced and the forest nodes of its
+/// right-hand side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packed {
+    pub rule: usize,
+    pub children: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ForestNode<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    Leaf(Token<Terminal>),
+    /// Every derivation of `lhs` from the same tokens. `span` runs from the first token to the
+    /// last, or is empty at the start of the next token when there are none.
+    Node {
+        lhs: NonTerminal,
+        span: Span,
+        alternatives: Vec<Packed>,
+    },
+}
+
+/// A shared packed parse forest: every parse of an input in one graph. A node stands for one
+/// nonterminal over one range of tokens and is shared by every derivation using it, and its
+/// alternatives are the different ways it can be derived, so an input with exponentially many
+/// parses still has a forest of polynomial size. Nodes refer to their children by index into
+/// [`Forest::nodes`]. A grammar with cyclic derivations gives a cyclic forest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forest<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    nodes: Vec<ForestNode<Terminal, NonTerminal>>,
+    root: usize,
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > Forest<Terminal, NonTerminal>
+{
+    /// Builds the forest of the nodes reachable from `root`. The others are dropped and the
+    /// remaining ones renumbered in the order they are first reached.
+    pub fn new(nodes: Vec<ForestNode<Terminal, NonTerminal>>, root: usize) -> Self {
+        let mut index = vec![None; nodes.len()];
+        let mut order = vec![];
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            if index[id].is_some() {
+                continue;
+            }
+            index[id] = Some(order.len());
+            order.push(id);
+            if let ForestNode::Node { alternatives, .. } = &nodes[id] {
+                for packed in alternatives.iter().rev() {
+                    stack.extend(packed.children.iter().rev());
+                }
+            }
+        }
+        let mut nodes: Vec<_> = nodes.into_iter().map(Some).collect();
+        let nodes = order
+            .into_iter()
+            .map(|id| {
+                let mut node = nodes[id].take().unwrap();
+                if let ForestNode::Node { alternatives, .. } = &mut node {
+                    for packed in alternatives {
+                        for child in &mut packed.children {
+                            *child = index[*child].unwrap();
+                        }
+                    }
+                }
+                node
+            })
+            .collect();
+        Self { nodes, root: 0 }
+    }
+    pub fn root(&self) -> usize {
+        self.root
+    }
+    pub fn node(&self, id: usize) -> &ForestNode<Terminal, NonTerminal> {
+        &self.nodes[id]
+    }
+    pub fn nodes(&self) -> &[ForestNode<Terminal, NonTerminal>] {
+        &self.nodes
+    }
+    /// The nodes with more than one derivation.
+    pub fn ambiguities(&self) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|id| {
+                matches!(&self.nodes[*id], ForestNode::Node { alternatives, .. }
+                    if alternatives.len() > 1)
+            })
+            .collect()
+    }
+    pub fn is_ambiguous(&self) -> bool {
+        !self.ambiguities().is_empty()
+    }
+    /// The only tree in the forest, or `None` if it is ambiguous.
+    pub fn tree(&self) -> Option<ParseTree<Terminal, NonTerminal>> {
+        if self.is_ambiguous() {
+            return None;
+        }
+        Some(self.tree_with(|_, _| 0))
+    }
+    /// The tree made of the alternative `choose` picks at every node, given the node and its
+    /// alternatives. The alternatives picked must not lead back to a node being built.
+    pub fn tree_with(
+        &self,
+        mut choose: impl FnMut(usize, &[Packed]) -> usize,
+    ) -> ParseTree<Terminal, NonTerminal> {
+        // Built bottom up with explicit stacks, so deep trees don't overflow the call stack. The
+        // trees of the children of a node are on top of `trees` when it is built.
+        let mut trees = vec![];
+        let mut stack = vec![(self.root, None)];
+        while let Some((id, chosen)) = stack.pop() {
+            match (&self.nodes[id], chosen) {
+                (ForestNode::Leaf(token), _) => trees.push(ParseTree::Leaf(token.clone())),
+                (ForestNode::Node { alternatives, .. }, None) => {
+                    let alternative = choose(id, alternatives);
+                    stack.push((id, Some(alternative)));
+                    stack.extend(
+                        alternatives[alternative]
+                            .children
+                            .iter()
+                            .rev()
+                            .map(|child| (*child, None)),
+                    );
+                }
+                (
+                    ForestNode::Node {
+                        lhs,
+                        span,
+                        alternatives,
+                    },
+                    Some(alternative),
+                ) => {
+                    let packed = &alternatives[alternative];
+                    let children = trees.split_off(trees.len() - packed.children.len());
+                    trees.push(ParseTree::node(*lhs, packed.rule, children, span));
+                }
+            }
+        }
+        trees.pop().unwrap()
+    }
+    /// Every tree in the forest. Their number can grow exponentially with the length of the
+    /// input, and derivations going through a cycle are left out.
+    pub fn trees(&self) -> Vec<ParseTree<Terminal, NonTerminal>> {
+        self.trees_of(self.root, &mut BTreeSet::new())
+    }
+    fn trees_of(
+        &self,
+        id: usize,
+        visiting: &mut BTreeSet<usize>,
+    ) -> Vec<ParseTree<Terminal, NonTerminal>> {
+        let (lhs, span, alternatives) = match &self.nodes[id] {
+            ForestNode::Leaf(token) => return vec![ParseTree::Leaf(token.clone())],
+            ForestNode::Node {
+                lhs,
+                span,
+                alternatives,
+            } => (lhs, span, alternatives),
+        };
+        if !visiting.insert(id) {
+            return vec![];
+        }
+        let mut res = vec![];
+        for packed in alternatives {
+            let mut partial: Vec<Vec<ParseTree<Terminal, NonTerminal>>> = vec![vec![]];
+            for child in &packed.children {
+                let trees = self.trees_of(*child, visiting);
+                partial = partial
+                    .iter()
+                    .flat_map(|children| {
+                        trees.iter().map(move |tree| {
+                            let mut children = children.clone();
+                            children.push(tree.clone());
+                            children
+                        })
+                    })
+                    .collect();
+            }
+            res.extend(
+                partial
+                    .into_iter()
+                    .map(|children| ParseTree::node(*lhs, packed.rule, children, span)),
+            );
+        }
+        visiting.remove(&id);
+        res
+    }
+}
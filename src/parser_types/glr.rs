@@ -0,0 +1,318 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use serde::Serialize;
+
+use super::{
+    action_goto::{Action, ParsingTable},
+    forest::{Forest, ForestNode, Packed},
+    nonterminal::NonTerminalTrait,
+    parse_error::ParseError,
+    rule::Rule,
+    terminal::TerminalTrait,
+    token::{Position, Span, Token},
+};
+
+/// A node of the graph-structured stack: a state pushed after reading the tokens before `level`.
+/// Each edge leads to a node below it and is labelled with the forest node of the symbol read in
+/// between.
+struct GssNode {
+    state: usize,
+    level: usize,
+    edges: Vec<(usize, usize)>,
+}
+
+/// A reduction to perform: the node it starts from, the rule, and the edge its paths must go
+/// through, if any.
+type Reduction = (usize, usize, Option<(usize, usize)>);
+
+/// A generalized LR parser, following every action of a conflicting cell of the parsing table
+/// instead of only the one kept in the action table. Where the deterministic parser would have
+/// to choose, the stack splits; stacks that end in the same state are merged again, so the
+/// stacks form a graph, and parses that reach the same reduction share the nodes of a single
+/// [`Forest`]. On a table without conflicts it builds the same tree as
+/// [`crate::parser_types::parser::Parser`], in time linear in the input; ambiguous grammars can
+/// take up to cubic time.
+///
+/// Reductions are performed as in Tomita's algorithm, with Nozohoor-Farshi's correction for
+/// empty rules: when a merge adds an edge below a node that was already reduced from, the
+/// reductions going through the new edge are performed again. There is no error recovery; the
+/// parse fails at the first token none of the stacks can shift.
+pub struct GlrParser<
+    'a,
+    Terminal: std::fmt::Debug
+        + Serialize
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    parsing_table: &'a ParsingTable<Terminal, NonTerminal>,
+    rules: &'a [Rule<Terminal, NonTerminal>],
+    initial_state: usize,
+}
+
+impl<
+        'a,
+        Terminal: std::fmt::Debug
+            + Serialize
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + Serialize
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > GlrParser<'a, Terminal, NonTerminal>
+{
+    pub fn new(
+        parsing_table: &'a ParsingTable<Terminal, NonTerminal>,
+        rules: &'a [Rule<Terminal, NonTerminal>],
+    ) -> Self {
+        Self {
+            parsing_table,
+            rules,
+            initial_state: 0,
+        }
+    }
+    /// Parses `entry_point` instead of the nonterminal the first start rule derives.
+    pub fn with_entry_point(
+        mut self,
+        entry_point: NonTerminal,
+    ) -> Result<Self, ParseError<Terminal, NonTerminal>> {
+        match self.parsing_table.entry_state(entry_point) {
+            Some(state) => {
+                self.initial_state = state;
+                Ok(self)
+            }
+            None => Err(ParseError::UnknownEntryPoint {
+                nonterminal: entry_point,
+            }),
+        }
+    }
+    /// Parses `lex_stream` up to its end-of-input token, or up to its end if it has none, into
+    /// the forest of every parse.
+    pub fn parse(
+        &self,
+        lex_stream: impl IntoIterator<Item = Token<Terminal>>,
+    ) -> Result<Forest<Terminal, NonTerminal>, ParseError<Terminal, NonTerminal>> {
+        let mut lex_stream = lex_stream.into_iter();
+        let mut gss = vec![GssNode {
+            state: self.initial_state,
+            level: 0,
+            edges: vec![],
+        }];
+        let mut forest = vec![];
+        // The nodes of the current level by state.
+        let mut frontier = BTreeMap::from([(self.initial_state, 0)]);
+        // Where the tokens read so far start and end.
+        let mut spans: Vec<Span> = vec![];
+        let mut end = Position::default();
+        loop {
+            let level = spans.len();
+            let token = lex_stream
+                .next()
+                .filter(|token| !token.kind.is_eof())
+                .unwrap_or_else(|| Token::eof(end));
+            // Forest nodes of the nonterminals derived so far from the tokens between two levels
+            // and ending at this one.
+            let mut symbols = BTreeMap::new();
+            let mut accepted = None;
+            let mut reductions = VecDeque::new();
+            for node in frontier.values() {
+                self.queue_reductions(&gss[*node], *node, token.kind, None, 0, &mut reductions);
+            }
+            while let Some((node, rule, edge)) = reductions.pop_front() {
+                let lhs = self.rules[rule].lhs;
+                for (below, children) in paths(&gss, node, self.rules[rule].rhs.len(), edge) {
+                    let from = gss[below].level;
+                    let Some(state) = self.parsing_table.goto(gss[below].state, lhs) else {
+                        return Err(ParseError::MissingGoto {
+                            state: gss[below].state,
+                            nonterminal: lhs,
+                        });
+                    };
+                    let symbol = *symbols.entry((lhs, from)).or_insert_with(|| {
+                        let span = if from < level {
+                            Span::new(spans[from].start, spans[level - 1].end)
+                        } else {
+                            Span::empty(token.span.start)
+                        };
+                        forest.push(ForestNode::Node {
+                            lhs,
+                            span,
+                            alternatives: vec![],
+                        });
+                        forest.len() - 1
+                    });
+                    let packed = Packed { rule, children };
+                    if let ForestNode::Node { alternatives, .. } = &mut forest[symbol] {
+                        if !alternatives.contains(&packed) {
+                            alternatives.push(packed);
+                        }
+                    }
+                    match frontier.get(&state) {
+                        Some(&target) => {
+                            if gss[target].edges.iter().any(|(to, _)| *to == below) {
+                                // The edge is labelled with `symbol` already.
+                                continue;
+                            }
+                            gss[target].edges.push((below, symbol));
+                            for node in frontier.values() {
+                                self.queue_reductions(
+                                    &gss[*node],
+                                    *node,
+                                    token.kind,
+                                    Some((target, below)),
+                                    1,
+                                    &mut reductions,
+                                );
+                            }
+                        }
+                        None => {
+                            gss.push(GssNode {
+                                state,
+                                level,
+                                edges: vec![(below, symbol)],
+                            });
+                            let target = gss.len() - 1;
+                            frontier.insert(state, target);
+                            self.queue_reductions(
+                                &gss[target],
+                                target,
+                                token.kind,
+                                None,
+                                0,
+                                &mut reductions,
+                            );
+                        }
+                    }
+                }
+            }
+
+            let mut shifts = BTreeMap::new();
+            for node in frontier.values() {
+                for action in self.parsing_table.actions(gss[*node].state, token.kind) {
+                    match action {
+                        Action::Shift(next) => {
+                            shifts
+                                .entry(next)
+                                .or_insert_with(BTreeSet::new)
+                                .insert(*node);
+                        }
+                        // The accepting state was reached from the initial state by reading the
+                        // entry point, whose forest node is the root.
+                        Action::Accept => accepted = gss[*node].edges.first().map(|edge| edge.1),
+                        Action::Reduce(_) => {}
+                    }
+                }
+            }
+            if let Some(root) = accepted {
+                return Ok(Forest::new(forest, root));
+            }
+            if shifts.is_empty() || token.kind.is_eof() {
+                let mut expected = BTreeSet::new();
+                for node in frontier.values() {
+                    expected.extend(self.parsing_table.expected_terminals(gss[*node].state));
+                }
+                return Err(ParseError::UnexpectedToken {
+                    state: gss[*frontier.values().next().unwrap()].state,
+                    token,
+                    expected: expected.into_iter().collect(),
+                });
+            }
+            end = token.span.end;
+            spans.push(token.span);
+            forest.push(ForestNode::Leaf(token));
+            let leaf = forest.len() - 1;
+            frontier.clear();
+            for (state, below) in shifts {
+                gss.push(GssNode {
+                    state,
+                    level: level + 1,
+                    edges: below.into_iter().map(|node| (node, leaf)).collect(),
+                });
+                frontier.insert(state, gss.len() - 1);
+            }
+        }
+    }
+    /// Queues the reductions `node` has on `lookahead` by rules at least `min_len` long, to be
+    /// performed along paths through `edge` if given.
+    fn queue_reductions(
+        &self,
+        node: &GssNode,
+        id: usize,
+        lookahead: Terminal,
+        edge: Option<(usize, usize)>,
+        min_len: usize,
+        reductions: &mut VecDeque<Reduction>,
+    ) {
+        for action in self.parsing_table.actions(node.state, lookahead) {
+            if let Action::Reduce(rule) = action {
+                if self.rules[rule].rhs.len() >= min_len {
+                    reductions.push_back((id, rule, edge));
+                }
+            }
+        }
+    }
+}
+
+/// The paths of `len` edges down from `node`, as the node each ends at and the labels along it in
+/// right-hand side order. Only paths through `edge` are returned if it is given.
+fn paths(
+    gss: &[GssNode],
+    node: usize,
+    len: usize,
+    edge: Option<(usize, usize)>,
+) -> Vec<(usize, Vec<usize>)> {
+    let mut paths = vec![(node, vec![], edge.is_none())];
+    for _ in 0..len {
+        paths = paths
+            .into_iter()
+            .flat_map(|(node, labels, through)| {
+                gss[node].edges.iter().filter_map(move |(to, label)| {
+                    let through = through || edge == Some((node, *to));
+                    // Edges only lead down, so a path below the edge can't go through it any more.
+                    if !through && edge.is_some_and(|(from, _)| gss[*to].level < gss[from].level) {
+                        return None;
+                    }
+                    let mut labels = labels.clone();
+                    labels.push(*label);
+                    Some((*to, labels, through))
+                })
+            })
+            .collect();
+    }
+    paths
+        .into_iter()
+        .filter(|(.., through)| *through)
+        .map(|(node, mut labels, _)| {
+            labels.reverse();
+            (node, labels)
+        })
+        .collect()
+}
@@ -0,0 +1,491 @@
+use serde::Serialize;
+
+use super::{
+    action_goto::{Action, ParseOutput},
+    nonterminal::NonTerminalTrait,
+    parse_tree::ParseTree,
+    parser::{Parser, Status},
+    terminal::TerminalTrait,
+    terminal_or_nonterminal::TerminalOrNonTerminal,
+    token::{Position, Span, Token},
+};
+
+/// A change to the source text: the text in `old_span` was replaced by text ending at `new_end`,
+/// and `tokens` is what the replacement lexes to. Every old token overlapping `old_span`, or
+/// strictly containing it when it is empty, is replaced by `tokens`, so `old_span` should be
+/// widened to the whole tokens whose text changed before relexing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+> {
+    pub old_span: Span,
+    pub new_end: Position,
+    pub tokens: Vec<Token<Terminal>>,
+}
+
+/// What is known about a node of the old tree, indexed in preorder.
+#[derive(Debug, Copy, Clone, Default)]
+struct NodeInfo {
+    /// Number of tokens below the node.
+    tokens: usize,
+    /// Number of nodes in the subtree, the node included.
+    size: usize,
+    /// Whether an `error` token was shifted somewhere below the node.
+    has_error: bool,
+    /// Whether the first token below the node is also the first thing reduced into it, i.e. no
+    /// empty node lies on its left edge.
+    starts_with_token: bool,
+}
+
+fn measure<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>(
+    tree: &ParseTree<Terminal, NonTerminal>,
+) -> Vec<NodeInfo> {
+    let mut nodes = vec![];
+    let mut parents = vec![];
+    let mut stack = vec![(tree, None)];
+    while let Some((tree, parent)) = stack.pop() {
+        let index = nodes.len();
+        nodes.push(tree);
+        parents.push(parent);
+        if let ParseTree::Node { children, .. } = tree {
+            stack.extend(children.iter().rev().map(|child| (child, Some(index))));
+        }
+    }
+    let mut infos = vec![NodeInfo::default(); nodes.len()];
+    for index in (0..nodes.len()).rev() {
+        let first_child = infos.get(index + 1).copied().unwrap_or_default();
+        let info = &mut infos[index];
+        info.size += 1;
+        match nodes[index] {
+            ParseTree::Leaf(token) => {
+                info.tokens = 1;
+                info.has_error = token.kind.is_error();
+                info.starts_with_token = true;
+            }
+            ParseTree::Node { children, .. } => {
+                info.starts_with_token =
+                    !children.is_empty() && first_child.tokens > 0 && first_child.starts_with_token;
+            }
+        }
+        let info = infos[index];
+        if let Some(parent) = parents[index] {
+            let parent = &mut infos[parent];
+            parent.tokens += info.tokens;
+            parent.size += info.size;
+            parent.has_error |= info.has_error;
+        }
+    }
+    infos
+}
+
+/// Moves `position`, which lies at or after `old_end`, to where the same text is after the text
+/// ending at `old_end` was changed to end at `new_end`.
+fn shift_position(position: Position, old_end: Position, new_end: Position) -> Position {
+    Position {
+        offset: new_end.offset + (position.offset - old_end.offset),
+        line: new_end.line + (position.line - old_end.line),
+        column: if position.line == old_end.line {
+            new_end.column + (position.column - old_end.column)
+        } else {
+            position.column
+        },
+    }
+}
+
+fn shift_span(span: Span, old_end: Position, new_end: Position) -> Span {
+    Span::new(
+        shift_position(span.start, old_end, new_end),
+        shift_position(span.end, old_end, new_end),
+    )
+}
+
+fn shift_tree<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>(
+    tree: &mut ParseTree<Terminal, NonTerminal>,
+    old_end: Position,
+    new_end: Position,
+) {
+    let mut stack = vec![tree];
+    while let Some(tree) = stack.pop() {
+        match tree {
+            ParseTree::Leaf(token) => token.span = shift_span(token.span, old_end, new_end),
+            ParseTree::Node { span, children, .. } => {
+                *span = shift_span(*span, old_end, new_end);
+                stack.extend(children.iter_mut());
+            }
+        }
+    }
+}
+
+impl<
+        'a,
+        Terminal: std::fmt::Debug
+            + Serialize
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + Serialize
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > Parser<'a, Terminal, NonTerminal>
+{
+    /// Parses the input of `old_tree` with `edit` applied, giving the same output as
+    /// [`Parser::parse`] on the edited tokens. `old_tree` must be the tree of an input parsed
+    /// without repairs, as inserted tokens can't be told apart from the input.
+    ///
+    /// The old tree is walked from the root. A subtree clear of the edit is shifted whole with
+    /// [`Parser::push_tree`] when the parser reaches it in the state it was built from and the
+    /// token after it is unchanged; any other subtree is taken apart into its children, down to
+    /// the tokens, which are pushed one at a time. The state each subtree was built from is
+    /// replayed from the gotos along the way rather than stored in the tree. If the edited input
+    /// has a syntax error the whole input is parsed again from scratch, so errors are recovered
+    /// from and repaired exactly as a fresh parse would.
+    pub fn reparse(
+        &mut self,
+        old_tree: &ParseTree<Terminal, NonTerminal>,
+        edit: &Edit<Terminal>,
+    ) -> ParseOutput<Terminal, NonTerminal> {
+        let infos = measure(old_tree);
+        let old_tokens = old_tree.tokens();
+        // Old tokens lo..hi are replaced by the edit.
+        let lo = old_tokens.partition_point(|token| token.span.end <= edit.old_span.start);
+        let hi = old_tokens.partition_point(|token| token.span.start < edit.old_span.end);
+        let hi = std::cmp::max(lo, hi);
+        let new_index = |old: usize| {
+            if old < lo {
+                old
+            } else {
+                old - hi + lo + edit.tokens.len()
+            }
+        };
+        let tokens: Vec<Token<Terminal>> = old_tokens[..lo]
+            .iter()
+            .map(|token| (*token).clone())
+            .chain(edit.tokens.iter().cloned())
+            .chain(old_tokens[hi..].iter().map(|token| {
+                let mut token = (*token).clone();
+                token.span = shift_span(token.span, edit.old_span.end, edit.new_end);
+                token
+            }))
+            .filter(|token| !token.kind.is_error())
+            .collect();
+        if infos[0].has_error {
+            self.reset();
+            return self.parse(tokens);
+        }
+
+        self.reset();
+        // Number of tokens of `tokens` pushed so far.
+        let mut next = 0;
+        let mut failed = false;
+        // Preorder index, the state the subtree was started from and the old number of its first
+        // token.
+        let mut stack = vec![(old_tree, 0, self.state(), 0)];
+        while let Some((tree, index, left_state, first)) = stack.pop() {
+            let info = infos[index];
+            let clear = first + info.tokens < lo || first >= hi;
+            match tree {
+                ParseTree::Leaf(_) => {
+                    if clear && !self.push_tokens(&tokens, &mut next, new_index(first) + 1) {
+                        failed = true;
+                        break;
+                    }
+                }
+                ParseTree::Node { children, .. } => {
+                    if info.tokens == 0 {
+                        continue;
+                    }
+                    if clear && info.starts_with_token {
+                        if !self.push_tokens(&tokens, &mut next, new_index(first)) {
+                            failed = true;
+                            break;
+                        }
+                        let mut tree = tree.clone();
+                        if first >= hi {
+                            shift_tree(&mut tree, edit.old_span.end, edit.new_end);
+                        }
+                        if self.push_tree(tree, left_state).is_ok() {
+                            next += info.tokens;
+                            continue;
+                        }
+                    }
+                    let mut child_stack = vec![];
+                    let (mut index, mut state, mut first) = (index + 1, Some(left_state), first);
+                    for child in children {
+                        let child_info = infos[index];
+                        child_stack.push((child, index, state.unwrap_or(usize::MAX), first));
+                        state = state.and_then(|state| match child.symbol() {
+                            TerminalOrNonTerminal::Terminal(terminal) => {
+                                match self.parsing_table().action(state, terminal) {
+                                    Some(Action::Shift(next)) => Some(next),
+                                    _ => None,
+                                }
+                            }
+                            TerminalOrNonTerminal::NonTerminal(nonterminal) => {
+                                self.parsing_table().goto(state, nonterminal)
+                            }
+                        });
+                        index += child_info.size;
+                        first += child_info.tokens;
+                    }
+                    stack.extend(child_stack.into_iter().rev());
+                }
+            }
+        }
+        let status = if !failed && self.push_tokens(&tokens, &mut next, tokens.len()) {
+            self.finish()
+        } else {
+            Status::NeedMore
+        };
+        match status {
+            Status::Accepted(tree) if self.errors().is_empty() => ParseOutput {
+                tree: Some(tree),
+                errors: vec![],
+                repairs: vec![],
+            },
+            _ => {
+                self.take_errors();
+                self.reset();
+                self.parse(tokens)
+            }
+        }
+    }
+    /// Pushes `tokens[*next..end]`, stopping at the first one that is not simply shifted.
+    fn push_tokens(&mut self, tokens: &[Token<Terminal>], next: &mut usize, end: usize) -> bool {
+        while *next < end {
+            let status = self.push(tokens[*next].clone());
+            *next += 1;
+            if !matches!(status, Status::NeedMore) || !self.errors().is_empty() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Edit;
+    use crate::{
+        grammar_file::{parse_grammar, NonTerminalName, TerminalName},
+        parser_types::{
+            action_goto::generate_parsing_table,
+            limits::ParseLimits,
+            parse_tree::ParseTree,
+            parser::{Parser, Status},
+            token::{Position, Span, Token},
+        },
+    };
+
+    const GRAMMAR: &str = "
+        Statements -> Statement | Statements Statement ;
+        Statement -> Sum semi | error semi ;
+        Sum -> Sum plus Product | Product ;
+        Product -> Product times Atom | Atom ;
+        Atom -> num | lparen Sum rparen ;
+    ";
+
+    fn lex(source: &str) -> Vec<Token<TerminalName<'static>>> {
+        let mut position = Position::default();
+        let mut tokens = vec![];
+        for word in source.split(' ') {
+            if !word.is_empty() {
+                let kind = match word {
+                    "+" => "plus",
+                    "*" => "times",
+                    "(" => "lparen",
+                    ")" => "rparen",
+                    ";" => "semi",
+                    _ => "num",
+                };
+                let start = position;
+                position = position.advance(word);
+                tokens.push(Token::new(
+                    TerminalName(kind),
+                    word,
+                    Span::new(start, position),
+                ));
+            }
+            position = position.advance(" ");
+        }
+        tokens
+    }
+
+    /// A xorshift generator, so the edits are the same on every run.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 11) as usize % n
+        }
+    }
+
+    #[test]
+    fn reparse_matches_parse() {
+        let grammar = parse_grammar(GRAMMAR).unwrap();
+        let rules = grammar.rules();
+        let parsing_table = generate_parsing_table(&rules, None);
+        let statements = [
+            "1 + 2 ;",
+            "3 * ( 4 + 5 ) ;",
+            "( 6 ) ;",
+            "7 ;",
+            "8 * 9 * 1 ;",
+        ];
+        let words = ["1", "+", "*", "(", ")", ";"];
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let (mut insertions, mut deletions, mut errors) = (0, 0, 0);
+        for _ in 0..1000 {
+            let count = 1 + rng.below(8);
+            let source: Vec<&str> = (0..count)
+                .map(|_| statements[rng.below(statements.len())])
+                .collect();
+            let source = source.join(" ");
+            let old_tokens = lex(&source);
+            let old = Parser::new(&parsing_table, &rules).parse(old_tokens.clone());
+            let old_tree = old.tree.unwrap();
+
+            // Replaces old tokens from..to with up to two words.
+            let from = rng.below(old_tokens.len());
+            let to = from + rng.below(3.min(old_tokens.len() - from + 1));
+            let replacement: Vec<&str> = (0..rng.below(3))
+                .map(|_| words[rng.below(words.len())])
+                .collect();
+            let mut replacement = replacement.join(" ");
+            let old_span = if to > from {
+                Span::new(old_tokens[from].span.start, old_tokens[to - 1].span.end)
+            } else {
+                if replacement.is_empty() {
+                    continue;
+                }
+                replacement.push(' ');
+                Span::empty(old_tokens[from].span.start)
+            };
+            let (start, end) = (old_span.start.offset, old_span.end.offset);
+            let new_source = format!("{}{}{}", &source[..start], replacement, &source[end..]);
+            let new_tokens = lex(&new_source);
+            let edit = Edit {
+                old_span,
+                new_end: Position::default().advance(&new_source[..start + replacement.len()]),
+                tokens: new_tokens
+                    .iter()
+                    .filter(|token| {
+                        token.span.start.offset >= start
+                            && token.span.end.offset <= start + replacement.len()
+                    })
+                    .cloned()
+                    .collect(),
+            };
+
+            let reparsed = Parser::new(&parsing_table, &rules).reparse(&old_tree, &edit);
+            let parsed = Parser::new(&parsing_table, &rules).parse(new_tokens);
+            assert_eq!(reparsed, parsed, "{:?} edited to {:?}", source, new_source);
+            insertions += usize::from(to == from);
+            deletions += usize::from(edit.tokens.is_empty());
+            errors += usize::from(!parsed.errors.is_empty());
+        }
+        assert!(insertions > 0 && deletions > 0 && errors > 0);
+    }
+
+    #[test]
+    fn push_tree_over_limit() {
+        let grammar = parse_grammar(GRAMMAR).unwrap();
+        let rules = grammar.rules();
+        let parsing_table = generate_parsing_table(&rules, None);
+        let tokens = lex("7 ; 1 + 2 ;");
+        let tree = Parser::new(&parsing_table, &rules)
+            .parse(tokens.clone())
+            .tree
+            .unwrap();
+        let ParseTree::Node { children, .. } = &tree else {
+            panic!("{:?}", tree);
+        };
+        let statement = children[1].clone();
+        let left_state = parsing_table
+            .goto(0, NonTerminalName("Statements"))
+            .unwrap();
+
+        // `7` takes three reductions and shifting `1 + 2 ;` as a whole two more, one over the
+        // limit.
+        let mut parser = Parser::new(&parsing_table, &rules).with_limits(ParseLimits {
+            max_reductions: Some(4),
+            ..ParseLimits::default()
+        });
+        for token in &tokens[..2] {
+            assert_eq!(parser.push(token.clone()), Status::NeedMore);
+        }
+        let state = parser.state();
+        assert_eq!(
+            parser.push_tree(statement, left_state),
+            Err(children[1].clone())
+        );
+        assert_eq!(parser.state(), state);
+
+        // The parser carries on as if the tree had never been offered.
+        let mut parser = parser.with_limits(ParseLimits::default());
+        for token in &tokens[2..] {
+            assert_eq!(parser.push(token.clone()), Status::NeedMore);
+        }
+        assert_eq!(parser.finish(), Status::Accepted(tree));
+    }
+}
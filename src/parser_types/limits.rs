@@ -0,0 +1,31 @@
+use std::fmt;
+
+/// Bounds on the resources one parse may use, for parsers exposed to untrusted input. Every
+/// limit is off by default.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ParseLimits {
+    /// Maximum number of states on the stack, which grows with the nesting of the input.
+    pub max_depth: Option<usize>,
+    /// Maximum number of tokens pushed, the end-of-input token excluded.
+    pub max_tokens: Option<usize>,
+    /// Maximum number of reductions performed.
+    pub max_reductions: Option<usize>,
+}
+
+/// The limit a parse ran into, with the value it was set to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Depth(usize),
+    Tokens(usize),
+    Reductions(usize),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Depth(max) => write!(f, "stack depth limit of {}", max),
+            Limit::Tokens(max) => write!(f, "token limit of {}", max),
+            Limit::Reductions(max) => write!(f, "reduction limit of {}", max),
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::marker::PhantomData;
 
+use serde::{Deserialize, Serialize};
+
 use super::{
     nonterminal::NonTerminalTrait, rule::Rule, terminal::TerminalTrait,
     terminal_or_nonterminal::TerminalOrNonTerminal,
@@ -32,7 +34,7 @@ pub struct LR1Item<
     phantom: PhantomData<NonTerminal>,
 }
 
-#[derive(Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct LR0Item {
     pub index: usize,
     pub dot_index: usize,
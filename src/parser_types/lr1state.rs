@@ -64,17 +64,24 @@ pub fn generate_lr1_statemachine<
     let mut n = 0;
     let mut i = 0;
     let firsts = crate::parser_utils::first::compute_firsts(rules);
-    let kernel = [LR1Item::new(n, 0, Terminal::eof())];
-    res.insert(
-        n,
-        LR1State {
-            state_number: n,
-            kernel: kernel.to_vec(),
-            items: lr1_closure(rules, &kernel, Some(&firsts)),
-            transitions: BTreeMap::new(),
-        },
-    );
-    n += 1;
+    // Every start rule gets an initial state, numbered in rule order from 0.
+    for (index, _) in rules
+        .iter()
+        .enumerate()
+        .filter(|(_, rule)| rule.lhs.is_start())
+    {
+        let kernel = [LR1Item::new(index, 0, Terminal::eof())];
+        res.insert(
+            n,
+            LR1State {
+                state_number: n,
+                kernel: kernel.to_vec(),
+                items: lr1_closure(rules, &kernel, Some(&firsts)),
+                transitions: BTreeMap::new(),
+            },
+        );
+        n += 1;
+    }
     while i < n {
         let mut state = res.get(&i).cloned().unwrap();
         let mut transitionable = BTreeSet::new();
@@ -1,8 +1,25 @@
 pub mod action_goto;
 pub mod augmented_grammar;
+pub mod compiled_parser;
+pub mod completion;
+pub mod disambiguation;
+pub mod earley;
+pub mod forest;
+pub mod glr;
+pub mod incremental;
+pub mod limits;
 pub mod lr1item;
 pub mod lr1state;
 pub mod nonterminal;
+pub mod parse_error;
+pub mod parse_tree;
+pub mod parser;
+pub mod reduce_hook;
+pub mod repair;
 pub mod rule;
+pub mod scannerless;
+pub mod stack;
 pub mod terminal;
 pub mod terminal_or_nonterminal;
+pub mod token;
+pub mod trace;
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use super::{
+    limits::Limit,
+    nonterminal::NonTerminalTrait,
+    parse_tree::ParseTree,
+    terminal::TerminalTrait,
+    token::{Span, Token},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    /// The action table has no entry for `token` in `state`. The
+    /// [`crate::parser_types::earley::EarleyParser`] has no states and gives the number of tokens
+    /// read before `token` instead.
+    UnexpectedToken {
+        state: usize,
+        token: Token<Terminal>,
+        expected: Vec<Terminal>,
+    },
+    /// The goto table has no entry for `nonterminal` in `state`. This only happens when the
+    /// parsing table was not generated from the rules given to the parser.
+    MissingGoto {
+        state: usize,
+        nonterminal: NonTerminal,
+    },
+    /// The parsing table has no start rule for `nonterminal`, so parsing can't start from it.
+    UnknownEntryPoint { nonterminal: NonTerminal },
+    /// The parse went over one of the limits set with
+    /// [`crate::parser_types::parser::Parser::with_limits`].
+    LimitExceeded { limit: Limit },
+    /// The filters of a [`crate::parser_types::disambiguation::Disambiguator`] left more than
+    /// one derivation of `nonterminal` over `span`. `alternatives` holds a tree for each.
+    Ambiguous {
+        nonterminal: NonTerminal,
+        span: Span,
+        alternatives: Vec<ParseTree<Terminal, NonTerminal>>,
+    },
+    /// The filters of a [`crate::parser_types::disambiguation::Disambiguator`] rejected every
+    /// derivation of the input.
+    NoDerivation {
+        nonterminal: NonTerminal,
+        span: Span,
+    },
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > fmt::Display for ParseError<Terminal, NonTerminal>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken {
+                token, expected, ..
+            } => write!(
+                f,
+                "unexpected {:?} {:?} at {}:{}, expected one of {:?}",
+                token.kind, token.lexeme, token.span.start.line, token.span.start.column, expected
+            ),
+            ParseError::MissingGoto { state, nonterminal } => {
+                write!(f, "no goto for {:?} in state {}", nonterminal, state)
+            }
+            ParseError::UnknownEntryPoint { nonterminal } => {
+                write!(f, "{:?} is not an entry point", nonterminal)
+            }
+            ParseError::LimitExceeded { limit } => write!(f, "{} exceeded", limit),
+            ParseError::Ambiguous {
+                nonterminal,
+                span,
+                alternatives,
+            } => write!(
+                f,
+                "ambiguous {:?} at {}:{}, {} derivations left",
+                nonterminal,
+                span.start.line,
+                span.start.column,
+                alternatives.len()
+            ),
+            ParseError::NoDerivation { nonterminal, span } => write!(
+                f,
+                "every derivation of {:?} at {}:{} was filtered out",
+                nonterminal, span.start.line, span.start.column
+            ),
+        }
+    }
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > std::error::Error for ParseError<Terminal, NonTerminal>
+{
+}
@@ -0,0 +1,159 @@
+use super::{
+    nonterminal::NonTerminalTrait,
+    terminal::TerminalTrait,
+    terminal_or_nonterminal::TerminalOrNonTerminal,
+    token::{Span, Token},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseTree<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    Leaf(Token<Terminal>),
+    Node {
+        lhs: NonTerminal,
+        /// Index of the reduced rule in the rules the parsing table was built from.
+        rule: usize,
+        span: Span,
+        children: Vec<ParseTree<Terminal, NonTerminal>>,
+    },
+}
+
+/// Nodes are dropped one at a time, so dropping a deep tree doesn't overflow the call stack.
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > Drop for ParseTree<Terminal, NonTerminal>
+{
+    fn drop(&mut self) {
+        let ParseTree::Node { children, .. } = self else {
+            return;
+        };
+        let mut trees = std::mem::take(children);
+        while let Some(mut tree) = trees.pop() {
+            if let ParseTree::Node { children, .. } = &mut tree {
+                trees.append(children);
+            }
+        }
+    }
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > ParseTree<Terminal, NonTerminal>
+{
+    /// Builds the node for a reduction. The span covers every child; a node without children
+    /// gets an empty span at `at`, normally the start of the lookahead token.
+    pub fn node(
+        lhs: NonTerminal,
+        rule: usize,
+        children: Vec<ParseTree<Terminal, NonTerminal>>,
+        at: &Span,
+    ) -> Self {
+        let span = match (children.first(), children.last()) {
+            (Some(first), Some(last)) => first.span().merge(&last.span()),
+            _ => Span::empty(at.start),
+        };
+        ParseTree::Node {
+            lhs,
+            rule,
+            span,
+            children,
+        }
+    }
+    pub fn span(&self) -> Span {
+        match self {
+            ParseTree::Leaf(token) => token.span,
+            ParseTree::Node { span, .. } => *span,
+        }
+    }
+    pub fn symbol(&self) -> TerminalOrNonTerminal<Terminal, NonTerminal> {
+        match self {
+            ParseTree::Leaf(token) => TerminalOrNonTerminal::Terminal(token.kind),
+            ParseTree::Node { lhs, .. } => TerminalOrNonTerminal::NonTerminal(*lhs),
+        }
+    }
+    /// The tokens at the leaves of the tree, left to right.
+    pub fn tokens(&self) -> Vec<&Token<Terminal>> {
+        let mut res = vec![];
+        let mut stack = vec![self];
+        while let Some(tree) = stack.pop() {
+            match tree {
+                ParseTree::Leaf(token) => res.push(token),
+                ParseTree::Node { children, .. } => stack.extend(children.iter().rev()),
+            }
+        }
+        res
+    }
+    /// The text of the tokens of the tree with their trivia. For a tree parsed from tokens
+    /// read with [`crate::lexing::lexer::Tokens::with_trivia`] this is the source text it was
+    /// parsed from, byte for byte.
+    pub fn to_source(&self) -> String {
+        let mut res = String::new();
+        for token in self.tokens() {
+            token.write_source(&mut res);
+        }
+        res
+    }
+    /// The leftmost token of the tree, if it has any.
+    pub fn first_token(&self) -> Option<&Token<Terminal>> {
+        let mut stack = vec![self];
+        while let Some(tree) = stack.pop() {
+            match tree {
+                ParseTree::Leaf(token) => return Some(token),
+                ParseTree::Node { children, .. } => stack.extend(children.iter().rev()),
+            }
+        }
+        None
+    }
+}
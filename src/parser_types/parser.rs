@@ -0,0 +1,877 @@
+use serde::Serialize;
+
+use std::collections::VecDeque;
+
+use super::{
+    action_goto::{Action, ParseOutput, ParsingTable},
+    limits::{Limit, ParseLimits},
+    nonterminal::NonTerminalTrait,
+    parse_error::ParseError,
+    parse_tree::ParseTree,
+    reduce_hook::ReduceHook,
+    repair::{find_repair, RepairConfig, RepairEdit},
+    rule::Rule,
+    stack::Stack,
+    terminal::TerminalTrait,
+    token::{Position, Span, Token},
+    trace::{ParseObserver, TraceEvent},
+};
+
+/// Number of tokens that must be shifted after an error before another error is reported.
+const RECOVERY_SHIFTS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    /// The token was shifted, or skipped while recovering from an error, and the parser is
+    /// waiting for the next one.
+    NeedMore,
+    Accepted(ParseTree<Terminal, NonTerminal>),
+    /// The token was rejected and the parser could not recover, or the token went over a limit.
+    /// Limits are checked before any reduction is made, so after one the parser is left as it was
+    /// before the token was pushed and a different token may be pushed instead. After a syntax
+    /// error recovery may have popped states and shifted the `error` terminal, so the parser
+    /// should be reset before it is used again.
+    Error(ParseError<Terminal, NonTerminal>),
+}
+
+/// A parser configuration captured by [`Parser::snapshot`].
+#[derive(Debug, Clone)]
+pub struct Snapshot<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    state_stack: Stack<usize>,
+    parse_stack: Stack<ParseTree<Terminal, NonTerminal>>,
+    end: Position,
+    errors: Vec<ParseError<Terminal, NonTerminal>>,
+    recovering: usize,
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > Snapshot<Terminal, NonTerminal>
+{
+    /// The end of the last token shifted before the snapshot was taken, where the input resumes.
+    pub fn position(&self) -> Position {
+        self.end
+    }
+}
+
+/// A push parser: tokens are fed one at a time with [`Parser::push`] and the end of the input is
+/// signalled with [`Parser::finish`].
+pub struct Parser<
+    'a,
+    Terminal: std::fmt::Debug
+        + Serialize
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    parsing_table: &'a ParsingTable<Terminal, NonTerminal>,
+    rules: &'a [Rule<Terminal, NonTerminal>],
+    state_stack: Stack<usize>,
+    parse_stack: Stack<ParseTree<Terminal, NonTerminal>>,
+    end: Position,
+    errors: Vec<ParseError<Terminal, NonTerminal>>,
+    /// Tokens left to shift before the parser is out of error recovery.
+    recovering: usize,
+    repair: Option<RepairConfig>,
+    observer: Option<&'a mut dyn ParseObserver<Terminal, NonTerminal>>,
+    reduce_hook: Option<&'a mut dyn ReduceHook<Terminal, NonTerminal>>,
+    limits: ParseLimits,
+    /// Tokens pushed and reductions performed since the parser was last reset.
+    tokens: usize,
+    reductions: usize,
+}
+
+impl<
+        'a,
+        Terminal: std::fmt::Debug
+            + Serialize
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + Serialize
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > Parser<'a, Terminal, NonTerminal>
+{
+    pub fn new(
+        parsing_table: &'a ParsingTable<Terminal, NonTerminal>,
+        rules: &'a [Rule<Terminal, NonTerminal>],
+    ) -> Self {
+        Self {
+            parsing_table,
+            rules,
+            state_stack: [0].into_iter().collect(),
+            parse_stack: Stack::new(),
+            end: Position::default(),
+            errors: vec![],
+            recovering: 0,
+            repair: Some(RepairConfig::default()),
+            observer: None,
+            reduce_hook: None,
+            limits: ParseLimits::default(),
+            tokens: 0,
+            reductions: 0,
+        }
+    }
+    /// Parses `entry_point` instead of the nonterminal the first start rule derives. The table
+    /// must have been generated with a start rule for it, see
+    /// [`crate::parser_types::augmented_grammar::AugmentedGrammar::entry_points`].
+    pub fn with_entry_point(
+        mut self,
+        entry_point: NonTerminal,
+    ) -> Result<Self, ParseError<Terminal, NonTerminal>> {
+        match self.parsing_table.entry_state(entry_point) {
+            Some(state) => {
+                self.state_stack = [state].into_iter().collect();
+                Ok(self)
+            }
+            None => Err(ParseError::UnknownEntryPoint {
+                nonterminal: entry_point,
+            }),
+        }
+    }
+    /// Reports every step of the parse to `observer`. Nothing is reported by default.
+    pub fn with_observer(
+        mut self,
+        observer: &'a mut dyn ParseObserver<Terminal, NonTerminal>,
+    ) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+    /// Calls `reduce_hook` on every reduction. The hook may feed back into the token source:
+    /// [`Parser::parse`] only reads the lookahead before reducing, except after a syntax error,
+    /// where it reads further ahead to find a repair.
+    pub fn with_reduce_hook(
+        mut self,
+        reduce_hook: &'a mut dyn ReduceHook<Terminal, NonTerminal>,
+    ) -> Self {
+        self.reduce_hook = Some(reduce_hook);
+        self
+    }
+    fn trace(&mut self, event: impl FnOnce() -> TraceEvent<Terminal, NonTerminal>) {
+        if let Some(observer) = self.observer.as_mut() {
+            observer.on_event(&event());
+        }
+    }
+    /// Makes the parse fail with [`ParseError::LimitExceeded`] once it goes over one of `limits`.
+    /// The token that went over is rejected, as with [`Status::Error`].
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+    /// Sets the limits of the error repair [`Parser::parse`] attempts, or turns it off with
+    /// `None`.
+    pub fn with_repair(mut self, repair: Option<RepairConfig>) -> Self {
+        self.repair = repair;
+        self
+    }
+    pub fn parsing_table(&self) -> &'a ParsingTable<Terminal, NonTerminal> {
+        self.parsing_table
+    }
+    /// The state numbers on the stack.
+    pub fn state_stack(&self) -> &Stack<usize> {
+        &self.state_stack
+    }
+    /// The trees built so far, one for every state above the bottom of the state stack.
+    pub fn parse_stack(&self) -> &Stack<ParseTree<Terminal, NonTerminal>> {
+        &self.parse_stack
+    }
+    /// Captures the parser's configuration so it can be brought back with [`Parser::restore`],
+    /// for instance to try another reading of an ambiguous construct. The stacks are shared with
+    /// the parser rather than copied, so taking many snapshots is cheap; a frame is only copied
+    /// when it is popped while a snapshot still holds it.
+    pub fn snapshot(&self) -> Snapshot<Terminal, NonTerminal> {
+        Snapshot {
+            state_stack: self.state_stack.clone(),
+            parse_stack: self.parse_stack.clone(),
+            end: self.end,
+            errors: self.errors.clone(),
+            recovering: self.recovering,
+        }
+    }
+    /// Puts the parser back in the configuration `snapshot` was taken in. The snapshot can be
+    /// restored again later.
+    pub fn restore(&mut self, snapshot: &Snapshot<Terminal, NonTerminal>) {
+        self.state_stack = snapshot.state_stack.clone();
+        self.parse_stack = snapshot.parse_stack.clone();
+        self.end = snapshot.end;
+        self.errors = snapshot.errors.clone();
+        self.recovering = snapshot.recovering;
+    }
+    pub fn state(&self) -> usize {
+        *self.state_stack.last().unwrap()
+    }
+    /// Performs every reduction `token` triggers and then shifts it. If the token is a syntax
+    /// error the parser recovers from it when the grammar has `error` rules to recover with. After
+    /// the input is accepted the parser is back in its initial configuration and can be reused.
+    pub fn push(&mut self, token: Token<Terminal>) -> Status<Terminal, NonTerminal> {
+        if let Some(max) = self
+            .limits
+            .max_tokens
+            .filter(|max| !token.kind.is_eof() && self.tokens >= *max)
+        {
+            return Status::Error(ParseError::LimitExceeded {
+                limit: Limit::Tokens(max),
+            });
+        }
+        let eof = token.kind.is_eof();
+        let status = self.push_token(token);
+        if !eof && !matches!(status, Status::Error(ParseError::LimitExceeded { .. })) {
+            self.tokens += 1;
+        }
+        status
+    }
+    fn push_token(&mut self, token: Token<Terminal>) -> Status<Terminal, NonTerminal> {
+        if let Err(state) = simulate(
+            self.parsing_table,
+            self.rules,
+            self.state_stack.iter().copied(),
+            token.kind,
+        ) {
+            self.trace(|| TraceEvent::Error {
+                state,
+                token: token.clone(),
+            });
+            return self.recover(token, state);
+        }
+        if let Err(err) = self.check_limits(self.state_stack.clone(), token.kind, self.reductions) {
+            return Status::Error(err);
+        }
+        if self.recovering > 0 {
+            self.recovering -= 1;
+        }
+        loop {
+            let state = self.state();
+            match self.parsing_table.action(state, token.kind) {
+                Some(Action::Shift(n)) => {
+                    self.trace(|| TraceEvent::Shift {
+                        state,
+                        next_state: n,
+                        token: token.clone(),
+                    });
+                    self.state_stack.push(n);
+                    self.end = token.span.end;
+                    self.parse_stack.push(ParseTree::Leaf(token));
+                    return Status::NeedMore;
+                }
+                Some(Action::Reduce(n)) => {
+                    if let Err(err) = self.reduce(n, &token) {
+                        return Status::Error(err);
+                    }
+                }
+                Some(Action::Accept) => {
+                    self.trace(|| TraceEvent::Accept { state });
+                    let tree = self.parse_stack.pop().unwrap();
+                    self.reset();
+                    return Status::Accepted(tree);
+                }
+                None => unreachable!("simulate accepted the token"),
+            }
+        }
+    }
+    /// Shifts `tree`, a subtree of an earlier parse, as a whole instead of its tokens. The tree is
+    /// only shifted if, after the reductions its first token triggers, the parser is in
+    /// `left_state`, the state the tree was started from when it was built. Reducing the same
+    /// tokens from the same state builds the same tree, provided the token after it is also the
+    /// same. Otherwise, or if the tree has no tokens, it is handed back.
+    pub fn push_tree(
+        &mut self,
+        tree: ParseTree<Terminal, NonTerminal>,
+        left_state: usize,
+    ) -> Result<(), ParseTree<Terminal, NonTerminal>> {
+        let (Some(first), ParseTree::Node { lhs, span, .. }) = (tree.first_token(), &tree) else {
+            return Err(tree);
+        };
+        let (first, lhs, end) = (first.clone(), *lhs, span.end);
+        // The reductions are followed on a copy of the states first, so the parser is only changed
+        // once the tree is known to fit.
+        let fits = self
+            .check_limits(self.state_stack.clone(), first.kind, self.reductions)
+            .is_ok_and(|state_stack| {
+                let state = *state_stack.last().unwrap();
+                state == left_state
+                    && self.parsing_table.goto(state, lhs).is_some()
+                    && self.check_depth(state_stack.len()).is_ok()
+            });
+        if !fits {
+            return Err(tree);
+        }
+        while let Some(Action::Reduce(n)) = self.parsing_table.action(self.state(), first.kind) {
+            if self.reduce(n, &first).is_err() {
+                return Err(tree);
+            }
+        }
+        let state = self.state();
+        let goto = self.parsing_table.goto(state, lhs).unwrap();
+        self.trace(|| TraceEvent::Reuse {
+            state,
+            nonterminal: lhs,
+            next_state: goto,
+        });
+        self.state_stack.push(goto);
+        self.parse_stack.push(tree);
+        self.end = end;
+        Ok(())
+    }
+    /// Pulls tokens from `lex_stream` until the input is accepted or an error can't be recovered
+    /// from. Unlike [`Parser::push`] this can look past an erroneous token, so errors are first
+    /// repaired with the cheapest edit that lets parsing continue, falling back to the `error`
+    /// rules when no repair is found.
+    pub fn parse(
+        &mut self,
+        lex_stream: impl IntoIterator<Item = Token<Terminal>>,
+    ) -> ParseOutput<Terminal, NonTerminal> {
+        let mut lex_stream = lex_stream.into_iter();
+        let mut output = ParseOutput {
+            tree: None,
+            errors: vec![],
+            repairs: vec![],
+        };
+        let mut buffer = VecDeque::new();
+        // Token number of buffer[0].
+        let mut index = 0;
+        let mut exhausted = false;
+        // Tokens produced by a repair, pushed before anything else is read from the buffer.
+        let mut repaired = VecDeque::new();
+        let mut terminals = None;
+        // Reads tokens until the buffer holds `wanted`, or up to the end of the input.
+        let mut fill = |buffer: &mut VecDeque<Token<Terminal>>,
+                        exhausted: &mut bool,
+                        wanted: usize,
+                        end: Position| {
+            while buffer.len() < wanted && !*exhausted {
+                let token = lex_stream
+                    .next()
+                    .unwrap_or_else(|| Token::eof(buffer.back().map_or(end, |t| t.span.end)));
+                *exhausted = token.kind.is_eof();
+                buffer.push_back(token);
+            }
+        };
+        loop {
+            let token = match repaired.pop_front() {
+                Some(token) => token,
+                None => {
+                    fill(&mut buffer, &mut exhausted, 1, self.end);
+                    if let Some(config) = &self.repair {
+                        if let Err(state) = simulate(
+                            self.parsing_table,
+                            self.rules,
+                            self.state_stack.iter().copied(),
+                            buffer.front().unwrap().kind,
+                        ) {
+                            // Only an error needs the tokens after it: room for the edits, the
+                            // tokens that must parse after them and as many again to rank the
+                            // repairs by.
+                            let wanted = config.max_cost + 2 * config.lookahead;
+                            fill(&mut buffer, &mut exhausted, wanted, self.end);
+                            let token = buffer.front().unwrap();
+                            let terminals = terminals.get_or_insert_with(|| {
+                                self.parsing_table
+                                    .terminals()
+                                    .into_iter()
+                                    .filter(|t| !t.is_eof() && !t.is_error())
+                                    .collect::<Vec<_>>()
+                            });
+                            let buffered: Vec<_> = buffer.iter().cloned().collect();
+                            if let Some(repair) = find_repair(
+                                self.parsing_table,
+                                self.rules,
+                                &self.state_stack.to_vec(),
+                                &buffered,
+                                index,
+                                terminals,
+                                config,
+                            ) {
+                                self.trace(|| TraceEvent::Error {
+                                    state,
+                                    token: token.clone(),
+                                });
+                                output.errors.push(ParseError::UnexpectedToken {
+                                    state,
+                                    expected: self.parsing_table.expected_terminals(state),
+                                    token: token.clone(),
+                                });
+                                for edit in &repair.edits {
+                                    let (RepairEdit::Insert { before: at, .. }
+                                    | RepairEdit::Delete { token: at }
+                                    | RepairEdit::Substitute { token: at, .. }) = *edit;
+                                    while index < at {
+                                        repaired.push_back(buffer.pop_front().unwrap());
+                                        index += 1;
+                                    }
+                                    match *edit {
+                                        RepairEdit::Insert { terminal, .. } => {
+                                            let at = buffer.front().unwrap().span.start;
+                                            repaired.push_back(Token::new(
+                                                terminal,
+                                                "",
+                                                Span::empty(at),
+                                            ));
+                                        }
+                                        RepairEdit::Delete { .. } => {
+                                            buffer.pop_front();
+                                            index += 1;
+                                        }
+                                        RepairEdit::Substitute { terminal, .. } => {
+                                            let mut token = buffer.pop_front().unwrap();
+                                            token.kind = terminal;
+                                            repaired.push_back(token);
+                                            index += 1;
+                                        }
+                                    }
+                                }
+                                output.repairs.push(repair);
+                                continue;
+                            }
+                        }
+                    }
+                    index += 1;
+                    buffer.pop_front().unwrap()
+                }
+            };
+            let status = self.push(token);
+            output.errors.extend(self.take_errors());
+            match status {
+                Status::NeedMore => {}
+                Status::Accepted(tree) => {
+                    output.tree = Some(tree);
+                    return output;
+                }
+                Status::Error(err) => {
+                    output.errors.push(err);
+                    return output;
+                }
+            }
+        }
+    }
+    /// Drops the stacks, back to the configuration of a new parser. Errors recorded so far are
+    /// kept until taken with [`Parser::take_errors`].
+    pub fn reset(&mut self) {
+        self.state_stack.truncate(1);
+        self.parse_stack.truncate(0);
+        self.end = Position::default();
+        self.recovering = 0;
+        self.tokens = 0;
+        self.reductions = 0;
+    }
+    /// Fails if pushing another state onto `len` states would go over the depth limit.
+    fn check_depth(&self, len: usize) -> Result<(), ParseError<Terminal, NonTerminal>> {
+        match self.limits.max_depth {
+            Some(max) if len >= max => Err(ParseError::LimitExceeded {
+                limit: Limit::Depth(max),
+            }),
+            _ => Ok(()),
+        }
+    }
+    /// Fails if `count` reductions would go over the reduction limit.
+    fn check_reductions(&self, count: usize) -> Result<(), ParseError<Terminal, NonTerminal>> {
+        match self.limits.max_reductions {
+            Some(max) if count > max => Err(ParseError::LimitExceeded {
+                limit: Limit::Reductions(max),
+            }),
+            _ => Ok(()),
+        }
+    }
+    /// Follows the reductions `terminal` triggers on top of `state_stack`, and the shift after
+    /// them, failing if they would go over the depth limit or take the count of reductions from
+    /// `reductions` over its limit. Limits are checked before the parser is changed, so a token
+    /// that goes over one leaves it as it was. Returns the states left after the reductions.
+    fn check_limits(
+        &self,
+        mut state_stack: Stack<usize>,
+        terminal: Terminal,
+        mut reductions: usize,
+    ) -> Result<Stack<usize>, ParseError<Terminal, NonTerminal>> {
+        loop {
+            match self
+                .parsing_table
+                .action(*state_stack.last().unwrap(), terminal)
+            {
+                Some(Action::Reduce(n)) => {
+                    reductions += 1;
+                    self.check_reductions(reductions)?;
+                    let rule = &self.rules[n];
+                    state_stack.truncate(state_stack.len() - rule.rhs.len());
+                    let state = *state_stack.last().unwrap();
+                    let Some(goto) = self.parsing_table.goto(state, rule.lhs) else {
+                        return Err(ParseError::MissingGoto {
+                            state,
+                            nonterminal: rule.lhs,
+                        });
+                    };
+                    self.check_depth(state_stack.len())?;
+                    state_stack.push(goto);
+                }
+                Some(Action::Shift(_)) => {
+                    self.check_depth(state_stack.len())?;
+                    return Ok(state_stack);
+                }
+                _ => return Ok(state_stack),
+            }
+        }
+    }
+    /// Signals the end of the input by pushing the end-of-input token.
+    pub fn finish(&mut self) -> Status<Terminal, NonTerminal> {
+        self.push(Token::eof(self.end))
+    }
+    /// Pops the right-hand side of rule `n` and pushes the node built from it. `lookahead` places
+    /// the node when the rule is empty.
+    fn reduce(
+        &mut self,
+        n: usize,
+        lookahead: &Token<Terminal>,
+    ) -> Result<(), ParseError<Terminal, NonTerminal>> {
+        self.reductions += 1;
+        let rule = &self.rules[n];
+        let (state, lhs) = (self.state(), rule.lhs);
+        self.trace(|| TraceEvent::Reduce {
+            state,
+            rule: n,
+            lhs,
+            lookahead: lookahead.kind,
+        });
+        let rule = &self.rules[n];
+        let split = self.parse_stack.len() - rule.rhs.len();
+        let children = self.parse_stack.split_off(split);
+        self.state_stack
+            .truncate(self.state_stack.len() - rule.rhs.len());
+        let state = self.state();
+        match self.parsing_table.goto(state, rule.lhs) {
+            Some(goto) => {
+                self.trace(|| TraceEvent::Goto {
+                    state,
+                    nonterminal: lhs,
+                    next_state: goto,
+                });
+                let node = ParseTree::node(lhs, n, children, &lookahead.span);
+                if let Some(reduce_hook) = self.reduce_hook.as_mut() {
+                    reduce_hook.on_reduce(&node, &self.parse_stack);
+                }
+                self.state_stack.push(goto);
+                self.parse_stack.push(node);
+                Ok(())
+            }
+            None => Err(ParseError::MissingGoto {
+                state,
+                nonterminal: rule.lhs,
+            }),
+        }
+    }
+    /// The syntax errors the parser recovered from so far.
+    pub fn errors(&self) -> &[ParseError<Terminal, NonTerminal>] {
+        &self.errors
+    }
+    pub fn take_errors(&mut self) -> Vec<ParseError<Terminal, NonTerminal>> {
+        std::mem::take(&mut self.errors)
+    }
+    /// Panic-mode recovery as in yacc: pop states until one can shift the `error` terminal, shift
+    /// it, then skip tokens until one can be shifted after it. States that reduce whatever the
+    /// lookahead are reduced first, as an LALR parser's default reductions would have done, so
+    /// complete constructs before the error are kept. Errors are only recorded once
+    /// `RECOVERY_SHIFTS` tokens have been shifted since the last one, so a single mistake is not
+    /// reported several times.
+    fn recover(&mut self, token: Token<Terminal>, state: usize) -> Status<Terminal, NonTerminal> {
+        let error = ParseError::UnexpectedToken {
+            state,
+            expected: self.parsing_table.expected_terminals(state),
+            token: token.clone(),
+        };
+        if self.recovering == RECOVERY_SHIFTS {
+            if token.kind.is_eof() {
+                return Status::Error(error);
+            }
+            if let Some(ParseTree::Leaf(error_token)) = self.parse_stack.last_mut() {
+                error_token.span.end = token.span.end;
+            }
+            return Status::NeedMore;
+        }
+        let mut state_stack = self.state_stack.clone();
+        let mut default_reductions = vec![];
+        while let Some(n) = self
+            .parsing_table
+            .default_reduction(*state_stack.last().unwrap())
+        {
+            let rule = &self.rules[n];
+            state_stack.truncate(state_stack.len() - rule.rhs.len());
+            match self
+                .parsing_table
+                .goto(*state_stack.last().unwrap(), rule.lhs)
+            {
+                Some(goto) => state_stack.push(goto),
+                None => break,
+            }
+            default_reductions.push(n);
+        }
+        let Some(above) = state_stack.iter().position(|state| {
+            matches!(
+                self.parsing_table.action(*state, Terminal::error()),
+                Some(Action::Shift(_))
+            )
+        }) else {
+            return Status::Error(error);
+        };
+        let depth = state_stack.len() - 1 - above;
+        if let Err(err) = self.check_recovery(&default_reductions, depth, token.kind) {
+            return Status::Error(err);
+        }
+        for n in default_reductions {
+            if let Err(err) = self.reduce(n, &token) {
+                return Status::Error(err);
+            }
+        }
+        if self.recovering == 0 {
+            self.errors.push(error);
+        }
+        self.recovering = RECOVERY_SHIFTS;
+        let popped = self.parse_stack.split_off(depth);
+        self.state_stack.truncate(depth + 1);
+        let start = popped
+            .first()
+            .map(|tree| tree.span().start)
+            .unwrap_or(token.span.start);
+        let state = self.state();
+        if let Some(Action::Shift(n)) = self.parsing_table.action(state, Terminal::error()) {
+            let error_token = Token::new(Terminal::error(), "", Span::new(start, token.span.start));
+            self.trace(|| TraceEvent::Shift {
+                state,
+                next_state: n,
+                token: error_token.clone(),
+            });
+            self.state_stack.push(n);
+            self.parse_stack.push(ParseTree::Leaf(error_token));
+        }
+        self.push_token(token)
+    }
+    /// Checks the limits against what [`Parser::recover`] is about to do: run
+    /// `default_reductions`, pop the states above `depth`, shift `error` and push `terminal`.
+    fn check_recovery(
+        &self,
+        default_reductions: &[usize],
+        depth: usize,
+        terminal: Terminal,
+    ) -> Result<(), ParseError<Terminal, NonTerminal>> {
+        let mut state_stack = self.state_stack.clone();
+        let mut reductions = self.reductions;
+        for &n in default_reductions {
+            reductions += 1;
+            self.check_reductions(reductions)?;
+            let rule = &self.rules[n];
+            state_stack.truncate(state_stack.len() - rule.rhs.len());
+            let goto = self
+                .parsing_table
+                .goto(*state_stack.last().unwrap(), rule.lhs)
+                .unwrap();
+            self.check_depth(state_stack.len())?;
+            state_stack.push(goto);
+        }
+        state_stack.truncate(depth + 1);
+        if let Some(Action::Shift(n)) = self
+            .parsing_table
+            .action(*state_stack.last().unwrap(), Terminal::error())
+        {
+            self.check_depth(state_stack.len())?;
+            state_stack.push(n);
+        }
+        if simulate(
+            self.parsing_table,
+            self.rules,
+            state_stack.iter().copied(),
+            terminal,
+        )
+        .is_ok()
+        {
+            self.check_limits(state_stack, terminal, reductions)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the reductions `terminal` triggers on top of `state_stack`, given top first, without
+/// touching it, and returns the action the terminal finally gets (a shift or accept), or the state
+/// in which it has no action.
+pub fn simulate<
+    Terminal: std::fmt::Debug
+        + Serialize
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>(
+    parsing_table: &ParsingTable<Terminal, NonTerminal>,
+    rules: &[Rule<Terminal, NonTerminal>],
+    state_stack: impl IntoIterator<Item = usize>,
+    terminal: Terminal,
+) -> Result<Action, usize> {
+    // The simulated stack is what is left of state_stack, whose top is base, followed by pushed.
+    let mut state_stack = state_stack.into_iter();
+    let mut base = state_stack.next().unwrap();
+    let mut pushed: Vec<usize> = vec![];
+    loop {
+        let state = *pushed.last().unwrap_or(&base);
+        match parsing_table.action(state, terminal) {
+            Some(Action::Reduce(n)) => {
+                let rule = &rules[n];
+                let mut len = rule.rhs.len();
+                let from_pushed = std::cmp::min(len, pushed.len());
+                pushed.truncate(pushed.len() - from_pushed);
+                len -= from_pushed;
+                for _ in 0..len {
+                    base = state_stack.next().unwrap();
+                }
+                let state = *pushed.last().unwrap_or(&base);
+                match parsing_table.goto(state, rule.lhs) {
+                    Some(goto) => pushed.push(goto),
+                    None => return Err(state),
+                }
+            }
+            Some(action) => return Ok(action),
+            None => return Err(state),
+        }
+    }
+}
+
+/// Like [`simulate`], but performs the reductions and the shift on `state_stack`.
+pub fn advance<
+    Terminal: std::fmt::Debug
+        + Serialize
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>(
+    parsing_table: &ParsingTable<Terminal, NonTerminal>,
+    rules: &[Rule<Terminal, NonTerminal>],
+    state_stack: &mut Vec<usize>,
+    terminal: Terminal,
+) -> Result<Action, usize> {
+    loop {
+        let state = *state_stack.last().unwrap();
+        match parsing_table.action(state, terminal) {
+            Some(Action::Shift(n)) => {
+                state_stack.push(n);
+                return Ok(Action::Shift(n));
+            }
+            Some(Action::Reduce(n)) => {
+                let rule = &rules[n];
+                state_stack.truncate(state_stack.len() - rule.rhs.len());
+                let state = *state_stack.last().unwrap();
+                match parsing_table.goto(state, rule.lhs) {
+                    Some(goto) => state_stack.push(goto),
+                    None => return Err(state),
+                }
+            }
+            Some(Action::Accept) => return Ok(Action::Accept),
+            None => return Err(state),
+        }
+    }
+}
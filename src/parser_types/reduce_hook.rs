@@ -0,0 +1,36 @@
+use super::{
+    nonterminal::NonTerminalTrait, parse_tree::ParseTree, stack::Stack, terminal::TerminalTrait,
+};
+
+/// Called by the parser on every reduction, for grammars whose token source depends on what has
+/// been parsed so far, like C's typedef names.
+pub trait ReduceHook<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>
+{
+    /// `node` is the node just built and `below` the parse stack under it. The
+    /// lookahead that triggered the reduction has already been read from the token source, but
+    /// nothing after it.
+    fn on_reduce(
+        &mut self,
+        node: &ParseTree<Terminal, NonTerminal>,
+        below: &Stack<ParseTree<Terminal, NonTerminal>>,
+    );
+}
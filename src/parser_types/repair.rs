@@ -0,0 +1,230 @@
+use std::{collections::HashSet, fmt};
+
+use serde::Serialize;
+
+use super::{
+    action_goto::{Action, ParsingTable},
+    nonterminal::NonTerminalTrait,
+    parser::advance,
+    rule::Rule,
+    terminal::TerminalTrait,
+    token::Token,
+};
+
+/// Limits for the repair search.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RepairConfig {
+    /// Maximum number of edits in a repair.
+    pub max_cost: usize,
+    /// Number of input tokens that must parse after the repair for it to be accepted.
+    pub lookahead: usize,
+}
+
+impl Default for RepairConfig {
+    fn default() -> Self {
+        Self {
+            max_cost: 3,
+            lookahead: 3,
+        }
+    }
+}
+
+/// A single edit of the input. Token numbers count every token pulled from the input, starting
+/// at 0.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RepairEdit<Terminal> {
+    Insert { terminal: Terminal, before: usize },
+    Delete { token: usize },
+    Substitute { token: usize, terminal: Terminal },
+}
+
+impl<Terminal: std::fmt::Debug> fmt::Display for RepairEdit<Terminal> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepairEdit::Insert { terminal, before } => {
+                write!(f, "insert `{:?}` before token {}", terminal, before)
+            }
+            RepairEdit::Delete { token } => write!(f, "delete token {}", token),
+            RepairEdit::Substitute { token, terminal } => {
+                write!(f, "replace token {} with `{:?}`", token, terminal)
+            }
+        }
+    }
+}
+
+/// The edits applied to the input to get past one syntax error, in input order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair<Terminal> {
+    pub edits: Vec<RepairEdit<Terminal>>,
+}
+
+impl<Terminal: std::fmt::Debug> fmt::Display for Repair<Terminal> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let edits: Vec<String> = self.edits.iter().map(|edit| edit.to_string()).collect();
+        write!(f, "{}", edits.join(", "))
+    }
+}
+
+struct Candidate<Terminal> {
+    state_stack: Vec<usize>,
+    /// Index in the buffer of the next input token.
+    next: usize,
+    edits: Vec<RepairEdit<Terminal>>,
+}
+
+/// Searches for the cheapest sequence of insertions, deletions and substitutions at the start of
+/// `buffer` after which `config.lookahead` more input tokens parse, in the style of Burke-Fisher
+/// and CPCT+ repair. Among the cheapest repairs the one after which most of `buffer` parses is
+/// chosen, ties going to insertions first, then substitutions, then deletions. `buffer` holds the
+/// erroneous token and the ones after it, `first_index` is the token number of `buffer[0]` and
+/// `terminals` are the terminals that may be inserted.
+pub fn find_repair<
+    Terminal: std::fmt::Debug
+        + Serialize
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>(
+    parsing_table: &ParsingTable<Terminal, NonTerminal>,
+    rules: &[Rule<Terminal, NonTerminal>],
+    state_stack: &[usize],
+    buffer: &[Token<Terminal>],
+    first_index: usize,
+    terminals: &[Terminal],
+    config: &RepairConfig,
+) -> Option<Repair<Terminal>> {
+    let mut seen = HashSet::new();
+    let mut frontier = vec![Candidate {
+        state_stack: state_stack.to_vec(),
+        next: 0,
+        edits: vec![],
+    }];
+    for _ in 0..config.max_cost {
+        let mut best: Option<(usize, Vec<RepairEdit<Terminal>>)> = None;
+        let mut next_frontier = vec![];
+        for candidate in &frontier {
+            let token = buffer.get(candidate.next);
+            let mut children = vec![];
+            for terminal in terminals {
+                let mut state_stack = candidate.state_stack.clone();
+                if let Ok(Action::Shift(_)) =
+                    advance(parsing_table, rules, &mut state_stack, *terminal)
+                {
+                    children.push((
+                        state_stack,
+                        candidate.next,
+                        RepairEdit::Insert {
+                            terminal: *terminal,
+                            before: first_index + candidate.next,
+                        },
+                    ));
+                }
+            }
+            if let Some(token) = token.filter(|token| !token.kind.is_eof()) {
+                for terminal in terminals {
+                    if *terminal == token.kind {
+                        continue;
+                    }
+                    let mut state_stack = candidate.state_stack.clone();
+                    if let Ok(Action::Shift(_)) =
+                        advance(parsing_table, rules, &mut state_stack, *terminal)
+                    {
+                        children.push((
+                            state_stack,
+                            candidate.next + 1,
+                            RepairEdit::Substitute {
+                                token: first_index + candidate.next,
+                                terminal: *terminal,
+                            },
+                        ));
+                    }
+                }
+                children.push((
+                    candidate.state_stack.clone(),
+                    candidate.next + 1,
+                    RepairEdit::Delete {
+                        token: first_index + candidate.next,
+                    },
+                ));
+            }
+            for (state_stack, next, edit) in children {
+                if !seen.insert((state_stack.clone(), next)) {
+                    continue;
+                }
+                let mut edits = candidate.edits.clone();
+                edits.push(edit);
+                let parsed = parses_ahead(parsing_table, rules, &state_stack, &buffer[next..]);
+                if parsed >= config.lookahead
+                    && best.as_ref().is_none_or(|(most, _)| parsed > *most)
+                {
+                    best = Some((parsed, edits.clone()));
+                }
+                next_frontier.push(Candidate {
+                    state_stack,
+                    next,
+                    edits,
+                });
+            }
+        }
+        if let Some((_, edits)) = best {
+            return Some(Repair { edits });
+        }
+        frontier = next_frontier;
+    }
+    None
+}
+
+/// How many tokens of `buffer` parse from `state_stack`. Accepting the input counts as parsing the
+/// whole buffer.
+fn parses_ahead<
+    Terminal: std::fmt::Debug
+        + Serialize
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>(
+    parsing_table: &ParsingTable<Terminal, NonTerminal>,
+    rules: &[Rule<Terminal, NonTerminal>],
+    state_stack: &[usize],
+    buffer: &[Token<Terminal>],
+) -> usize {
+    let mut state_stack = state_stack.to_vec();
+    for (parsed, token) in buffer.iter().enumerate() {
+        match advance(parsing_table, rules, &mut state_stack, token.kind) {
+            Ok(Action::Shift(_)) => {}
+            Ok(_) => return usize::MAX,
+            Err(_) => return parsed,
+        }
+    }
+    buffer.len()
+}
@@ -0,0 +1,439 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::lexing::regex::{next_char, Regex, RegexError};
+
+use super::{
+    action_goto::{generate_parsing_table, Action, ParseOutput},
+    augmented_grammar::AugmentedGrammar,
+    compiled_parser::CompiledParser,
+    nonterminal::NonTerminalTrait,
+    rule::Rule,
+    terminal::TerminalTrait,
+    terminal_or_nonterminal::TerminalOrNonTerminal,
+    token::{Position, Span, Token},
+};
+
+/// A terminal of a scannerless grammar: a character, or an inclusive range of characters.
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Char {
+    Eof,
+    Error,
+    Range(char, char),
+}
+
+impl TerminalTrait for Char {
+    fn is_eof(&self) -> bool {
+        self == &Char::Eof
+    }
+    fn eof() -> Self {
+        Char::Eof
+    }
+    fn is_error(&self) -> bool {
+        self == &Char::Error
+    }
+    fn error() -> Self {
+        Char::Error
+    }
+}
+
+impl Char {
+    pub fn single(c: char) -> Self {
+        Char::Range(c, c)
+    }
+    /// The ranges of a character set written as in a [`Regex`], such as `[a-zA-Z_]`, `[^"\\]`
+    /// or `\d`. A rule can only hold one range, so a set of several needs a rule for each.
+    pub fn set(pattern: &str) -> Result<Vec<Self>, RegexError> {
+        match Regex::parse(pattern)? {
+            Regex::Set(ranges) => Ok(ranges
+                .into_iter()
+                .map(|(lo, hi)| Char::Range(lo, hi))
+                .collect()),
+            _ => Err(RegexError {
+                pattern: pattern.to_string(),
+                offset: 0,
+                message: "not a single character set".to_string(),
+            }),
+        }
+    }
+}
+
+/// The terminal a scannerless grammar is parsed with: the number of a class of characters that
+/// no terminal of the grammar tells apart, see [`CharClasses`].
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CharClass {
+    Eof,
+    Error,
+    Class(usize),
+}
+
+impl TerminalTrait for CharClass {
+    fn is_eof(&self) -> bool {
+        self == &CharClass::Eof
+    }
+    fn eof() -> Self {
+        CharClass::Eof
+    }
+    fn is_error(&self) -> bool {
+        self == &CharClass::Error
+    }
+    fn error() -> Self {
+        CharClass::Error
+    }
+}
+
+/// The nonterminal a scannerless grammar is parsed with: one of the grammar's, or one standing
+/// for a range of characters that spans several classes, with a rule for each of its classes.
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CharNonTerminal<NonTerminal> {
+    Grammar(NonTerminal),
+    Range(char, char),
+}
+
+impl<
+        NonTerminal: std::fmt::Debug
+            + Serialize
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > NonTerminalTrait for CharNonTerminal<NonTerminal>
+{
+    fn is_start(&self) -> bool {
+        matches!(self, CharNonTerminal::Grammar(nt) if nt.is_start())
+    }
+    fn start() -> Self {
+        CharNonTerminal::Grammar(NonTerminal::start())
+    }
+}
+
+/// A partition of the characters into classes, each holding the characters that are in exactly
+/// the same of the ranges it was built from. A class need not be contiguous: with the ranges
+/// `a-z` and `x`, the letters other than `x` form one class whichever side of `x` they are on.
+/// The characters in none of the ranges form a class too.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharClasses {
+    /// The first character of every run of characters in the same class, in increasing order.
+    starts: Vec<char>,
+    /// The class of every run.
+    classes: Vec<usize>,
+    len: usize,
+}
+
+impl CharClasses {
+    pub fn new(ranges: &[(char, char)]) -> Self {
+        let mut starts = BTreeSet::from(['\0']);
+        for (lo, hi) in ranges {
+            starts.insert(*lo);
+            starts.extend(next_char(*hi));
+        }
+        let starts: Vec<char> = starts.into_iter().collect();
+        let mut ids: BTreeMap<Vec<usize>, usize> = BTreeMap::new();
+        let classes = starts
+            .iter()
+            .map(|start| {
+                let inside: Vec<usize> = (0..ranges.len())
+                    .filter(|index| (ranges[*index].0..=ranges[*index].1).contains(start))
+                    .collect();
+                let next = ids.len();
+                *ids.entry(inside).or_insert(next)
+            })
+            .collect();
+        Self {
+            starts,
+            classes,
+            len: ids.len(),
+        }
+    }
+    /// The number of classes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn class(&self, c: char) -> usize {
+        self.classes[self.starts.partition_point(|start| *start <= c) - 1]
+    }
+    /// The classes the characters from `lo` to `hi` are in, in increasing order.
+    pub fn classes_in(&self, lo: char, hi: char) -> Vec<usize> {
+        let first = self.starts.partition_point(|start| *start <= lo) - 1;
+        let last = self.starts.partition_point(|start| *start <= hi);
+        let classes: BTreeSet<usize> = self.classes[first..last].iter().copied().collect();
+        classes.into_iter().collect()
+    }
+    /// A token for every character of `source`.
+    pub fn tokens<'s>(&'s self, source: &'s str) -> impl Iterator<Item = Token<CharClass>> + 's {
+        let mut at = Position::default();
+        source.chars().map(move |c| {
+            let mut buffer = [0; 4];
+            let text = c.encode_utf8(&mut buffer);
+            let start = at;
+            at = at.advance(text);
+            Token::new(
+                CharClass::Class(self.class(c)),
+                &*text,
+                Span::new(start, at),
+            )
+        })
+    }
+}
+
+/// A grammar over characters, parsed without a lexer, with the restrictions that make up for
+/// not having one: tokens are made of several characters, so where one ends has to be decided by
+/// the grammar.
+///
+/// Rules can't be empty, as everywhere else in the crate, so optional whitespace has to be
+/// written out in the rules that allow it.
+pub struct ScannerlessGrammar<
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    rules: Vec<Rule<Char, NonTerminal>>,
+    /// The characters every nonterminal can't be followed by.
+    follow_restrictions: BTreeMap<NonTerminal, Vec<(char, char)>>,
+    longest_match: BTreeSet<NonTerminal>,
+}
+
+impl<
+        NonTerminal: std::fmt::Debug
+            + Serialize
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > ScannerlessGrammar<NonTerminal>
+{
+    pub fn new(grammar: &AugmentedGrammar<Char, NonTerminal>) -> Self {
+        Self {
+            rules: grammar.rules(),
+            follow_restrictions: BTreeMap::new(),
+            longest_match: BTreeSet::new(),
+        }
+    }
+    /// Forbids `nonterminal` from being directly followed by any of the characters of
+    /// `ranges`: once it is parsed, nothing is reduced with one of them next, so they can only
+    /// be shifted. Restricting an identifier by the characters it is made of, for instance,
+    /// stops it from ending in the middle of a word, while still letting a left-recursive rule
+    /// such as `Ident -> Ident Letter` extend it.
+    ///
+    /// Only reductions are forbidden: where the grammar can shift one of the characters right
+    /// after `nonterminal`, as in `Pair -> Ident Ident`, the conflict this causes is left in the
+    /// table.
+    pub fn with_follow_restriction(
+        mut self,
+        nonterminal: NonTerminal,
+        ranges: &[(char, char)],
+    ) -> Self {
+        self.follow_restrictions
+            .entry(nonterminal)
+            .or_default()
+            .extend_from_slice(ranges);
+        self
+    }
+    /// Makes `nonterminal` match as many characters as it can: once it is parsed, wherever the
+    /// next character could be shifted or something reduced instead, it is shifted. Unlike a
+    /// follow restriction, this only takes effect where the two conflict.
+    pub fn with_longest_match(mut self, nonterminal: NonTerminal) -> Self {
+        self.longest_match.insert(nonterminal);
+        self
+    }
+    /// Groups the characters into classes and generates the parsing table over them. Every rule
+    /// of the grammar is kept once: a range that spans several classes is replaced by a
+    /// [`CharNonTerminal::Range`] with a rule for each of its classes, shared by every rule the
+    /// range is in, while a range within one class is replaced by that class. The parser decides
+    /// which range a character is by the character after it, so ranges that share classes in the
+    /// same place of the grammar can conflict where the grammar's rules alone would not.
+    pub fn build(&self) -> Scannerless<NonTerminal> {
+        let mut ranges: Vec<(char, char)> = self
+            .follow_restrictions
+            .values()
+            .flatten()
+            .copied()
+            .collect();
+        for rule in &self.rules {
+            for symbol in &rule.rhs {
+                if let TerminalOrNonTerminal::Terminal(Char::Range(lo, hi)) = symbol {
+                    ranges.push((*lo, *hi));
+                }
+            }
+        }
+        let classes = CharClasses::new(&ranges);
+
+        let mut rules = vec![];
+        let mut origins = vec![];
+        // The ranges that span several classes, in the order they are first used.
+        let mut spanning: Vec<(char, char)> = vec![];
+        for (index, rule) in self.rules.iter().enumerate() {
+            let rhs = rule
+                .rhs
+                .iter()
+                .map(|symbol| match symbol {
+                    TerminalOrNonTerminal::NonTerminal(nt) => {
+                        TerminalOrNonTerminal::NonTerminal(CharNonTerminal::Grammar(*nt))
+                    }
+                    TerminalOrNonTerminal::Terminal(Char::Eof) => {
+                        TerminalOrNonTerminal::Terminal(CharClass::Eof)
+                    }
+                    TerminalOrNonTerminal::Terminal(Char::Error) => {
+                        TerminalOrNonTerminal::Terminal(CharClass::Error)
+                    }
+                    TerminalOrNonTerminal::Terminal(Char::Range(lo, hi)) => {
+                        match classes.classes_in(*lo, *hi).as_slice() {
+                            [class] => TerminalOrNonTerminal::Terminal(CharClass::Class(*class)),
+                            _ => {
+                                if !spanning.contains(&(*lo, *hi)) {
+                                    spanning.push((*lo, *hi));
+                                }
+                                TerminalOrNonTerminal::NonTerminal(CharNonTerminal::Range(*lo, *hi))
+                            }
+                        }
+                    }
+                })
+                .collect();
+            rules.push(Rule {
+                lhs: CharNonTerminal::Grammar(rule.lhs),
+                rhs,
+            });
+            origins.push(Some(index));
+        }
+        for (lo, hi) in spanning {
+            for class in classes.classes_in(lo, hi) {
+                rules.push(Rule {
+                    lhs: CharNonTerminal::Range(lo, hi),
+                    rhs: vec![TerminalOrNonTerminal::Terminal(CharClass::Class(class))],
+                });
+                origins.push(None);
+            }
+        }
+
+        let mut parsing_table = generate_parsing_table(&rules, None);
+        let restricted: BTreeMap<CharNonTerminal<NonTerminal>, BTreeSet<usize>> = self
+            .follow_restrictions
+            .iter()
+            .map(|(nt, ranges)| {
+                let classes = ranges
+                    .iter()
+                    .flat_map(|(lo, hi)| classes.classes_in(*lo, *hi))
+                    .collect();
+                (CharNonTerminal::Grammar(*nt), classes)
+            })
+            .collect();
+        let mut removed = vec![];
+        for (state, terminal, _) in parsing_table.action_cells() {
+            // Every kernel item of a state has the symbol it was entered on just before the dot,
+            // so the reductions of a state entered on a nonterminal are what follows it.
+            let Some(TerminalOrNonTerminal::NonTerminal(after)) = parsing_table
+                .kernel(state)
+                .first()
+                .and_then(|item| item.dot_index.checked_sub(1).map(|i| (item.index, i)))
+                .map(|(rule, i)| rules[rule].rhs[i])
+            else {
+                continue;
+            };
+            let follows = match terminal {
+                CharClass::Class(class) => restricted
+                    .get(&after)
+                    .is_some_and(|set| set.contains(&class)),
+                _ => false,
+            };
+            let actions = parsing_table.actions(state, terminal);
+            let shifts = actions
+                .iter()
+                .any(|action| matches!(action, Action::Shift(_)));
+            for action in actions {
+                let longest = matches!(after, CharNonTerminal::Grammar(nt) if self.longest_match.contains(&nt));
+                if matches!(action, Action::Reduce(_)) && (follows || (shifts && longest)) {
+                    removed.push((state, terminal, action));
+                }
+            }
+        }
+        for (state, terminal, action) in removed {
+            parsing_table.remove_action(state, terminal, action);
+        }
+        Scannerless {
+            classes,
+            parser: CompiledParser::new(rules, parsing_table),
+            origins,
+        }
+    }
+}
+
+/// A scannerless grammar ready to parse text directly, see [`ScannerlessGrammar::build`].
+#[derive(Clone, PartialEq)]
+pub struct Scannerless<
+    NonTerminal: std::fmt::Debug
+        + Serialize
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    classes: CharClasses,
+    parser: CompiledParser<CharClass, CharNonTerminal<NonTerminal>>,
+    /// The rule of the grammar every rule of `parser` was made from, if any.
+    origins: Vec<Option<usize>>,
+}
+
+impl<
+        NonTerminal: std::fmt::Debug
+            + Serialize
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > Scannerless<NonTerminal>
+{
+    pub fn classes(&self) -> &CharClasses {
+        &self.classes
+    }
+    /// The rules over character classes and their parsing table. The rule numbers in parse
+    /// trees and errors are numbers of these rules, see [`Scannerless::origin`].
+    pub fn parser(&self) -> &CompiledParser<CharClass, CharNonTerminal<NonTerminal>> {
+        &self.parser
+    }
+    /// The number of the rule of the grammar that `rule` of [`Scannerless::parser`] was made
+    /// from, or `None` for the rules of a [`CharNonTerminal::Range`].
+    pub fn origin(&self, rule: usize) -> Option<usize> {
+        self.origins[rule]
+    }
+    /// Parses `source` from `entry_point`, a character at a time.
+    pub fn parse(
+        &self,
+        source: &str,
+        entry_point: NonTerminal,
+    ) -> ParseOutput<CharClass, CharNonTerminal<NonTerminal>> {
+        self.parser.parse(
+            self.classes.tokens(source),
+            CharNonTerminal::Grammar(entry_point),
+        )
+    }
+}
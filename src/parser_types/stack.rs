@@ -0,0 +1,161 @@
+use std::rc::Rc;
+
+struct Frame<T> {
+    value: T,
+    below: Option<Rc<Frame<T>>>,
+}
+
+impl<T: Clone> Clone for Frame<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            below: self.below.clone(),
+        }
+    }
+}
+
+/// A persistent stack: a linked list of reference counted frames, so cloning it is O(1) and
+/// clones share every frame they have in common. A frame is only copied when it is popped while
+/// another clone still holds it.
+pub struct Stack<T> {
+    top: Option<Rc<Frame<T>>>,
+    len: usize,
+}
+
+impl<T> Clone for Stack<T> {
+    fn clone(&self) -> Self {
+        Self {
+            top: self.top.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self { top: None, len: 0 }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Stack<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut values: Vec<&T> = self.iter().collect();
+        values.reverse();
+        f.debug_list().entries(values).finish()
+    }
+}
+
+/// Frames are dropped one at a time, so dropping a deep stack doesn't overflow the call stack.
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        let mut top = self.top.take();
+        while let Some(frame) = top {
+            match Rc::try_unwrap(frame) {
+                Ok(mut frame) => top = frame.below.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl<T> Stack<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn push(&mut self, value: T) {
+        self.top = Some(Rc::new(Frame {
+            value,
+            below: self.top.take(),
+        }));
+        self.len += 1;
+    }
+    pub fn last(&self) -> Option<&T> {
+        self.top.as_ref().map(|frame| &frame.value)
+    }
+    /// The values from the top of the stack down.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            frame: self.top.as_deref(),
+        }
+    }
+}
+
+impl<T: Clone> Stack<T> {
+    pub fn pop(&mut self) -> Option<T> {
+        let frame = self.top.take()?;
+        self.len -= 1;
+        match Rc::try_unwrap(frame) {
+            Ok(frame) => {
+                self.top = frame.below;
+                Some(frame.value)
+            }
+            Err(frame) => {
+                self.top = frame.below.clone();
+                Some(frame.value.clone())
+            }
+        }
+    }
+    /// The top value, copying its frame first if it is shared.
+    pub fn last_mut(&mut self) -> Option<&mut T> {
+        self.top
+            .as_mut()
+            .map(|frame| &mut Rc::make_mut(frame).value)
+    }
+    /// Pops values until `len` are left, without copying shared frames.
+    pub fn truncate(&mut self, len: usize) {
+        while self.len > len {
+            let frame = self.top.take().unwrap();
+            self.top = match Rc::try_unwrap(frame) {
+                Ok(mut frame) => frame.below.take(),
+                Err(frame) => frame.below.clone(),
+            };
+            self.len -= 1;
+        }
+    }
+    /// Pops the values above the first `at`, returning them bottom first.
+    pub fn split_off(&mut self, at: usize) -> Vec<T> {
+        let mut res = Vec::with_capacity(self.len.saturating_sub(at));
+        while self.len > at {
+            res.push(self.pop().unwrap());
+        }
+        res.reverse();
+        res
+    }
+    /// The values from the bottom of the stack up.
+    pub fn to_vec(&self) -> Vec<T> {
+        let mut res: Vec<T> = self.iter().cloned().collect();
+        res.reverse();
+        res
+    }
+}
+
+/// Builds a stack from values bottom first.
+impl<T: Clone> FromIterator<T> for Stack<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut res = Self::new();
+        for value in iter {
+            res.push(value);
+        }
+        res
+    }
+}
+
+pub struct Iter<'s, T> {
+    frame: Option<&'s Frame<T>>,
+}
+
+impl<'s, T> Iterator for Iter<'s, T> {
+    type Item = &'s T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.frame?;
+        self.frame = frame.below.as_deref();
+        Some(&frame.value)
+    }
+}
@@ -26,4 +26,8 @@
 pub trait TerminalTrait {
     fn is_eof(&self) -> bool;
     fn eof() -> Self;
+    /// Whether this is the `error` pseudo-terminal. Rules may use it like any other terminal;
+    /// the parser shifts it in place of the input it skips while recovering from a syntax error.
+    fn is_error(&self) -> bool;
+    fn error() -> Self;
 }
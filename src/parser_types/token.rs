@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+use super::terminal::TerminalTrait;
+
+/// A location in the source text. `offset` is a byte offset, `line` and `column` are 1-based and
+/// `column` counts characters rather than bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl Position {
+    pub fn new(offset: usize, line: usize, column: usize) -> Self {
+        Self {
+            offset,
+            line,
+            column,
+        }
+    }
+    /// The position reached after reading `text` starting from `self`.
+    pub fn advance(&self, text: &str) -> Self {
+        let mut res = *self;
+        for c in text.chars() {
+            res.offset += c.len_utf8();
+            if c == '\n' {
+                res.line += 1;
+                res.column = 1;
+            } else {
+                res.column += 1;
+            }
+        }
+        res
+    }
+}
+
+/// A half-open range `start..end` of the source text.
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+    /// A zero-width span at `at`, used for tokens and nodes that cover no text.
+    pub fn empty(at: Position) -> Self {
+        Self { start: at, end: at }
+    }
+    /// The smallest span covering both `self` and `other`.
+    pub fn merge(&self, other: &Span) -> Self {
+        Self {
+            start: std::cmp::min(self.start, other.start),
+            end: std::cmp::max(self.end, other.end),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.end.offset - self.start.offset
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Source text a lexer skips between tokens, such as whitespace and comments. Tokens keep it so
+/// a tree can be printed back exactly as it was read.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Trivia {
+    pub text: String,
+    pub span: Span,
+    /// The number of the skip pattern that matched the text, to tell comments from whitespace.
+    pub pattern: usize,
+}
+
+/// The trivia around a token.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct TokenTrivia {
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Token<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+> {
+    pub kind: Terminal,
+    pub lexeme: String,
+    pub span: Span,
+    /// The trivia around the token, when the lexer keeps it. Boxed, as most tokens have none.
+    pub trivia: Option<Box<TokenTrivia>>,
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+    > Token<Terminal>
+{
+    pub fn new(kind: Terminal, lexeme: impl Into<String>, span: Span) -> Self {
+        Self {
+            kind,
+            lexeme: lexeme.into(),
+            span,
+            trivia: None,
+        }
+    }
+    pub fn leading(&self) -> &[Trivia] {
+        self.trivia.as_ref().map_or(&[], |trivia| &trivia.leading)
+    }
+    pub fn trailing(&self) -> &[Trivia] {
+        self.trivia.as_ref().map_or(&[], |trivia| &trivia.trailing)
+    }
+    pub fn trivia_mut(&mut self) -> &mut TokenTrivia {
+        self.trivia.get_or_insert_with(Default::default)
+    }
+    /// Appends the lexeme to `out` with the trivia around it.
+    pub fn write_source(&self, out: &mut String) {
+        for trivia in self.leading() {
+            out.push_str(&trivia.text);
+        }
+        out.push_str(&self.lexeme);
+        for trivia in self.trailing() {
+            out.push_str(&trivia.text);
+        }
+    }
+    /// The end-of-input token, placed at `at`.
+    pub fn eof(at: Position) -> Self {
+        Self::new(Terminal::eof(), "", Span::empty(at))
+    }
+}
+
+/// A token without text, for callers that only have terminal kinds at hand.
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+    > From<Terminal> for Token<Terminal>
+{
+    fn from(kind: Terminal) -> Self {
+        Self::new(kind, "", Span::default())
+    }
+}
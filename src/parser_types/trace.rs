@@ -0,0 +1,173 @@
+use std::fmt;
+
+use super::{nonterminal::NonTerminalTrait, terminal::TerminalTrait, token::Token};
+
+/// One step of the parser, as reported to a [`ParseObserver`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+> {
+    Shift {
+        state: usize,
+        next_state: usize,
+        token: Token<Terminal>,
+    },
+    /// `state` reduces by `rule` on `lookahead`. A [`TraceEvent::Goto`] follows once the
+    /// right-hand side is popped.
+    Reduce {
+        state: usize,
+        rule: usize,
+        lhs: NonTerminal,
+        lookahead: Terminal,
+    },
+    Goto {
+        state: usize,
+        nonterminal: NonTerminal,
+        next_state: usize,
+    },
+    /// A subtree of an earlier parse was shifted whole instead of its tokens.
+    Reuse {
+        state: usize,
+        nonterminal: NonTerminal,
+        next_state: usize,
+    },
+    Accept {
+        state: usize,
+    },
+    /// `token` has no action in `state`. What follows depends on whether the error is repaired or
+    /// recovered from.
+    Error {
+        state: usize,
+        token: Token<Terminal>,
+    },
+}
+
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > fmt::Display for TraceEvent<Terminal, NonTerminal>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceEvent::Shift {
+                state,
+                next_state,
+                token,
+            } => write!(
+                f,
+                "shift {:?} {:?}: {} -> {}",
+                token.kind, token.lexeme, state, next_state
+            ),
+            TraceEvent::Reduce {
+                state,
+                rule,
+                lhs,
+                lookahead,
+            } => write!(
+                f,
+                "reduce r{} to {:?} on {:?} in {}",
+                rule, lhs, lookahead, state
+            ),
+            TraceEvent::Goto {
+                state,
+                nonterminal,
+                next_state,
+            } => write!(f, "goto {:?}: {} -> {}", nonterminal, state, next_state),
+            TraceEvent::Reuse {
+                state,
+                nonterminal,
+                next_state,
+            } => write!(f, "reuse {:?}: {} -> {}", nonterminal, state, next_state),
+            TraceEvent::Accept { state } => write!(f, "accept in {}", state),
+            TraceEvent::Error { state, token } => write!(
+                f,
+                "error on {:?} {:?} in {}",
+                token.kind, token.lexeme, state
+            ),
+        }
+    }
+}
+
+pub trait ParseObserver<
+    Terminal: std::fmt::Debug
+        + TerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + Ord
+        + PartialOrd,
+    NonTerminal: std::fmt::Debug
+        + NonTerminalTrait
+        + Copy
+        + Clone
+        + PartialEq
+        + std::hash::Hash
+        + Eq
+        + PartialOrd
+        + Ord,
+>
+{
+    fn on_event(&mut self, event: &TraceEvent<Terminal, NonTerminal>);
+}
+
+/// Records every event, for stepping through a parse after the fact.
+impl<
+        Terminal: std::fmt::Debug
+            + TerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + Ord
+            + PartialOrd,
+        NonTerminal: std::fmt::Debug
+            + NonTerminalTrait
+            + Copy
+            + Clone
+            + PartialEq
+            + std::hash::Hash
+            + Eq
+            + PartialOrd
+            + Ord,
+    > ParseObserver<Terminal, NonTerminal> for Vec<TraceEvent<Terminal, NonTerminal>>
+{
+    fn on_event(&mut self, event: &TraceEvent<Terminal, NonTerminal>) {
+        self.push(event.clone());
+    }
+}